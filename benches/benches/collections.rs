@@ -0,0 +1,147 @@
+use std::collections::BTreeSet;
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use fast_stm::{atomically, TList, TSet, TVar};
+
+const N_THREADS: u64 = 4;
+
+/// Fine-grained collections against a coarse `TVar<BTreeSet<_>>`
+pub fn criterion_benchmark(c: &mut Criterion) {
+    let sizes = [10_u64, 100, 1_000];
+
+    // G1
+
+    let mut g1 = c.benchmark_group("set-insert-times");
+    for n in sizes {
+        g1.throughput(Throughput::Elements(n));
+        g1.bench_with_input(BenchmarkId::new("TSet::<u64>::insert", n), &n, |b, &n| {
+            b.iter(|| {
+                let set = TSet::new();
+                for i in 0..n {
+                    atomically(|tx| set.insert(tx, i));
+                }
+                set
+            });
+        });
+        g1.bench_with_input(
+            BenchmarkId::new("TVar::<BTreeSet<u64>>::modify", n),
+            &n,
+            |b, &n| {
+                b.iter(|| {
+                    let set = TVar::new(BTreeSet::new());
+                    for i in 0..n {
+                        atomically(|tx| {
+                            set.modify(tx, |mut s| {
+                                s.insert(i);
+                                s
+                            })
+                        });
+                    }
+                    set
+                });
+            },
+        );
+        g1.bench_with_input(
+            BenchmarkId::new("TList::<u64>::push_front", n),
+            &n,
+            |b, &n| {
+                b.iter(|| {
+                    let list = TList::new();
+                    for i in 0..n {
+                        atomically(|tx| list.push_front(tx, i));
+                    }
+                    list
+                });
+            },
+        );
+    }
+    g1.finish();
+
+    // G2
+
+    let mut g2 = c.benchmark_group("set-contains-times");
+    for n in sizes {
+        let tset = TSet::new();
+        let tlist = TList::new();
+        let tbtree = TVar::new((0..n).collect::<BTreeSet<_>>());
+        atomically(|tx| {
+            for i in 0..n {
+                tset.insert(tx, i)?;
+                tlist.push_front(tx, i)?;
+            }
+            Ok(())
+        });
+
+        g2.bench_with_input(BenchmarkId::new("TSet::<u64>::contains", n), &n, |b, &n| {
+            b.iter(|| atomically(|tx| tset.contains(tx, black_box(&(n / 2)))));
+        });
+        g2.bench_with_input(
+            BenchmarkId::new("TVar::<BTreeSet<u64>>::read", n),
+            &n,
+            |b, &n| {
+                b.iter(|| atomically(|tx| Ok(tbtree.read(tx)?.contains(black_box(&(n / 2))))));
+            },
+        );
+        g2.bench_with_input(
+            BenchmarkId::new("TList::<u64>::contains", n),
+            &n,
+            |b, &n| {
+                b.iter(|| atomically(|tx| tlist.contains(tx, black_box(&(n / 2)))));
+            },
+        );
+    }
+    g2.finish();
+
+    // G3
+
+    let mut g3 = c.benchmark_group("set-concurrent-insert-times");
+    for n in [10_u64, 100] {
+        g3.throughput(Throughput::Elements(n * N_THREADS));
+        g3.bench_with_input(BenchmarkId::new("TSet::<u64>::insert", n), &n, |b, &n| {
+            b.iter(|| {
+                let set = TSet::new();
+                std::thread::scope(|s| {
+                    for t in 0..N_THREADS {
+                        let set = &set;
+                        s.spawn(move || {
+                            for i in 0..n {
+                                atomically(|tx| set.insert(tx, i * N_THREADS + t));
+                            }
+                        });
+                    }
+                });
+                set
+            });
+        });
+        g3.bench_with_input(
+            BenchmarkId::new("TVar::<BTreeSet<u64>>::modify", n),
+            &n,
+            |b, &n| {
+                b.iter(|| {
+                    let set = TVar::new(BTreeSet::new());
+                    std::thread::scope(|s| {
+                        for t in 0..N_THREADS {
+                            let set = &set;
+                            s.spawn(move || {
+                                for i in 0..n {
+                                    atomically(|tx| {
+                                        set.modify(tx, |mut s| {
+                                            s.insert(i * N_THREADS + t);
+                                            s
+                                        })
+                                    });
+                                }
+                            });
+                        }
+                    });
+                    set
+                });
+            },
+        );
+    }
+    g3.finish();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
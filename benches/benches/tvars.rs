@@ -1,7 +1,7 @@
 use std::hint::black_box;
 
 use criterion::{criterion_group, criterion_main, Criterion};
-use fast_stm::{init_transaction, TVar};
+use fast_stm::{init_transaction, TArray, TVar};
 
 #[allow(unused)]
 #[derive(Debug, Clone, Copy)]
@@ -70,6 +70,15 @@ pub fn tvar_wrapping(c: &mut Criterion) {
         b.iter(|| black_box(TVar::new(String::from("STM!"))))
     });
     g3.finish();
+
+    let mut g4 = c.benchmark_group("tarray-init-times");
+    g4.bench_function("Vec::<TVar<u32>>::new (1000 elements)", |b| {
+        b.iter(|| black_box((0..1000_u32).map(TVar::new).collect::<Vec<_>>()))
+    });
+    g4.bench_function("TArray::<u32>::new (1000 elements)", |b| {
+        b.iter(|| black_box(TArray::new(0..1000_u32)))
+    });
+    g4.finish();
 }
 
 // TODO: TVar<u32> vs TVarU32
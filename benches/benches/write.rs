@@ -46,11 +46,11 @@ pub fn criterion_benchmark(c: &mut Criterion) {
     });
     g1.bench_function("TVar::<u32>::write_atomic", |b| {
         let tvar = TVar::new(old_value);
-        b.iter(|| black_box(tvar.write_atomic(new_value)))
+        b.iter(|| tvar.write_atomic(black_box(new_value)))
     });
     g1.bench_function("AtomicU32::store", |b| {
         let atom = AtomicU32::new(old_value);
-        b.iter(|| black_box(atom.store(new_value, Ordering::Relaxed)))
+        b.iter(|| atom.store(black_box(new_value), Ordering::Relaxed))
     });
     g1.bench_function("RwLock::write", |b| {
         let lock = RwLock::new(old_value);
@@ -13,6 +13,24 @@ fn set_rustc_channel_cfg() -> &'static str {
     "stable"
 }
 
+/// Features that need to identify variables, e.g. to report them.
+const DIAGNOSTICS_FEATURES: &[&str] = &[
+    "PROFILING",
+    "TRACING",
+    "WATCHDOG",
+    "EVENT_LOG",
+    "WAIT_ON_RETRY",
+];
+
 fn main() {
     println!("cargo:rustc-cfg={}", set_rustc_channel_cfg());
+
+    // `diagnostics` enables `VarId` and the labels of variables.
+    println!("cargo::rustc-check-cfg=cfg(diagnostics)");
+    if DIAGNOSTICS_FEATURES
+        .iter()
+        .any(|feature| std::env::var_os(format!("CARGO_FEATURE_{feature}")).is_some())
+    {
+        println!("cargo:rustc-cfg=diagnostics");
+    }
 }
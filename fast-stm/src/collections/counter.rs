@@ -0,0 +1,181 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::{StmClosureResult, TArray, Transaction};
+
+/// Return the shard index of the current thread.
+///
+/// Threads are assigned indices in a round-robin fashion, the first time they use a counter.
+fn thread_index() -> usize {
+    static NEXT_INDEX: AtomicUsize = AtomicUsize::new(0);
+    thread_local!(static INDEX: usize = NEXT_INDEX.fetch_add(1, Ordering::Relaxed));
+
+    INDEX.with(|idx| *idx)
+}
+
+/// A transactional counter, sharded to avoid conflicts between increments.
+///
+/// A counter stored in a single `TVar` serializes all transactions incrementing it. `TCounter`
+/// splits its value across multiple shards, each thread incrementing its own shard. Moreover,
+/// increments are commutative updates (see [`Transaction::commute`]), applied to the current
+/// value of the shard at commit time. As long as a transaction does not read the counter, its
+/// increments never conflict with other transactions.
+///
+/// Reading the counter sums all the shards, and therefore conflicts with any concurrent
+/// increment.
+///
+/// Cloning a `TCounter` yields a new handle to the same counter, like `TVar`.
+///
+/// ```
+/// # use fast_stm::*;
+/// let commits = TCounter::new();
+///
+/// atomically(|trans| {
+///     // ...
+///     commits.increment(trans)
+/// });
+///
+/// assert_eq!(commits.read_atomic(), 1);
+/// ```
+#[derive(Clone)]
+pub struct TCounter {
+    shards: TArray<u64>,
+}
+
+impl TCounter {
+    /// Create a new counter, with one shard per available CPU.
+    pub fn new() -> Self {
+        let n_shards = std::thread::available_parallelism().map_or(1, usize::from);
+        Self::with_shards(n_shards)
+    }
+
+    /// Create a new counter with `n_shards` shards.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n_shards` is zero.
+    pub fn with_shards(n_shards: usize) -> Self {
+        assert!(n_shards > 0, "TCounter needs at least one shard");
+        Self {
+            shards: TArray::new(std::iter::repeat_n(0, n_shards)),
+        }
+    }
+
+    /// Increment the counter by one.
+    pub fn increment(&self, transaction: &mut Transaction) -> StmClosureResult<()> {
+        self.add(transaction, 1)
+    }
+
+    /// Increment the counter by `amount`.
+    ///
+    /// The counter wraps around on overflow.
+    pub fn add(&self, transaction: &mut Transaction, amount: u64) -> StmClosureResult<()> {
+        let shard = thread_index() % self.shards.len();
+        self.shards
+            .commute(transaction, shard, move |x| x.wrapping_add(amount))
+    }
+
+    /// Read the value of the counter.
+    ///
+    /// This reads every shard of the counter.
+    pub fn read(&self, transaction: &mut Transaction) -> StmClosureResult<u64> {
+        let mut sum = 0_u64;
+        for shard in 0..self.shards.len() {
+            sum = sum.wrapping_add(self.shards.read(transaction, shard)?);
+        }
+        Ok(sum)
+    }
+
+    /// `read_atomic` reads the value of the counter, without starting a transaction.
+    ///
+    /// Shards are read one after the other, so the result may not reflect a state the counter
+    /// has been in if it is incremented at the same time.
+    ///
+    /// <div class="warning">
+    ///
+    /// This method should not be used inside transactions.
+    ///
+    /// </div>
+    pub fn read_atomic(&self) -> u64 {
+        (0..self.shards.len())
+            .map(|shard| self.shards.read_atomic(shard))
+            .fold(0, u64::wrapping_add)
+    }
+}
+
+impl Default for TCounter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::atomically;
+
+    #[test]
+    fn increment_read() {
+        let counter = TCounter::with_shards(4);
+
+        let x = atomically(|tx| {
+            counter.increment(tx)?;
+            counter.add(tx, 41)?;
+            counter.read(tx)
+        });
+        assert_eq!(x, 42);
+
+        // increments after a read are applied on top of the read value
+        let x = atomically(|tx| {
+            let x = counter.read(tx)?;
+            counter.add(tx, 8)?;
+            Ok(x)
+        });
+        assert_eq!(x, 42);
+        assert_eq!(counter.read_atomic(), 50);
+    }
+
+    /// Blind increments of the same shard should not conflict.
+    #[test]
+    fn increments_do_not_conflict() {
+        let counter = TCounter::with_shards(1);
+
+        let mut tx1 = Transaction::default();
+        let mut tx2 = Transaction::default();
+        counter.add(&mut tx1, 1).unwrap();
+        counter.add(&mut tx2, 2).unwrap();
+        assert!(tx2.commit());
+        assert!(tx1.commit());
+        assert_eq!(counter.read_atomic(), 3);
+
+        // reading makes the transaction conflict with concurrent increments
+        let mut tx1 = Transaction::default();
+        let mut tx2 = Transaction::default();
+        counter.read(&mut tx1).unwrap();
+        counter.add(&mut tx1, 1).unwrap();
+        counter.add(&mut tx2, 2).unwrap();
+        assert!(tx2.commit());
+        assert!(!tx1.commit());
+        assert_eq!(counter.read_atomic(), 5);
+    }
+
+    #[test]
+    fn threaded_increments() {
+        let counter = TCounter::with_shards(2);
+
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let counter = counter.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..1000 {
+                        atomically(|tx| counter.increment(tx));
+                    }
+                })
+            })
+            .collect();
+        for h in handles {
+            h.join().unwrap();
+        }
+
+        assert_eq!(counter.read_atomic(), 4000);
+    }
+}
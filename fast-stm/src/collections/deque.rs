@@ -0,0 +1,399 @@
+use std::any::Any;
+use std::fmt::{self, Debug};
+use std::sync::Arc;
+
+use crate::{retry, StmClosureResult, TVar, Transaction};
+
+/// Link to the next node of a stack.
+type Link<T> = Option<Arc<Node<T>>>;
+
+struct Node<T> {
+    value: T,
+    next: Link<T>,
+}
+
+impl<T> Drop for Node<T> {
+    fn drop(&mut self) {
+        // Dropping a node drops its successor if this was the last reference to it, which
+        // recurses through the whole stack. Unlink the chain iteratively instead.
+        let mut link = self.next.take();
+        while let Some(node) = link {
+            match Arc::try_unwrap(node) {
+                Ok(mut node) => link = node.next.take(),
+                Err(_) => break,
+            }
+        }
+    }
+}
+
+/// A persistent stack, holding one end of the deque.
+struct Stack<T> {
+    top: Link<T>,
+    len: usize,
+}
+
+impl<T> Clone for Stack<T> {
+    fn clone(&self) -> Self {
+        Self {
+            top: self.top.clone(),
+            len: self.len,
+        }
+    }
+}
+
+impl<T> Stack<T>
+where
+    T: Clone,
+{
+    fn empty() -> Self {
+        Self { top: None, len: 0 }
+    }
+
+    /// Build a stack from its elements, starting from the bottom.
+    fn from_bottom(values: impl IntoIterator<Item = T>) -> Self {
+        values
+            .into_iter()
+            .fold(Self::empty(), |stack, value| stack.push(value))
+    }
+
+    fn push(&self, value: T) -> Self {
+        Self {
+            top: Some(Arc::new(Node {
+                value,
+                next: self.top.clone(),
+            })),
+            len: self.len + 1,
+        }
+    }
+
+    fn pop(&self) -> Option<(T, Self)> {
+        self.top.as_ref().map(|node| {
+            let rest = Self {
+                top: node.next.clone(),
+                len: self.len - 1,
+            };
+            (node.value.clone(), rest)
+        })
+    }
+
+    /// Return the element at the bottom of the stack, walking through all of its nodes.
+    fn bottom(&self) -> Option<&T> {
+        let mut node = self.top.as_ref()?;
+        while let Some(next) = &node.next {
+            node = next;
+        }
+        Some(&node.value)
+    }
+
+    /// Return the elements of the stack, starting from the top.
+    fn to_vec(&self) -> Vec<T> {
+        let mut values = Vec::with_capacity(self.len);
+        let mut link = &self.top;
+        while let Some(node) = link {
+            values.push(node.value.clone());
+            link = &node.next;
+        }
+        values
+    }
+}
+
+/// A transactional unbounded double-ended queue.
+///
+/// The deque is made of two persistent stacks, one for each end, stored in their own `TVar`s.
+/// Pushing or popping an element only touches the stack of its end, so operations on opposite
+/// ends do not conflict in the common case. When popping from an end whose stack is empty, half
+/// of the other stack is moved over, which conflicts with operations on the other end.
+///
+/// Cloning a `TDeque` yields a new handle to the same deque, like `TVar`.
+///
+/// ```
+/// # use fast_stm::*;
+/// let deque = TDeque::new();
+///
+/// atomically(|trans| {
+///     deque.push_back(trans, 2)?;
+///     deque.push_back(trans, 3)?;
+///     deque.push_front(trans, 1)
+/// });
+///
+/// assert_eq!(atomically(|trans| deque.pop_back(trans)), 3);
+/// assert_eq!(atomically(|trans| deque.to_vec(trans)), [1, 2]);
+/// ```
+pub struct TDeque<T> {
+    front: TVar<Stack<T>>,
+    back: TVar<Stack<T>>,
+}
+
+impl<T> TDeque<T>
+where
+    T: Any + Send + Sync + Clone,
+{
+    /// Create an empty deque.
+    pub fn new() -> Self {
+        Self {
+            front: TVar::new(Stack::empty()),
+            back: TVar::new(Stack::empty()),
+        }
+    }
+
+    /// Push an element at the front of the deque.
+    pub fn push_front(&self, transaction: &mut Transaction, value: T) -> StmClosureResult<()> {
+        self.front.modify(transaction, |stack| stack.push(value))
+    }
+
+    /// Push an element at the back of the deque.
+    pub fn push_back(&self, transaction: &mut Transaction, value: T) -> StmClosureResult<()> {
+        self.back.modify(transaction, |stack| stack.push(value))
+    }
+
+    /// Remove the first element of the deque and return it.
+    ///
+    /// Calls `retry` if the deque is empty, blocking the transaction until an element is pushed.
+    pub fn pop_front(&self, transaction: &mut Transaction) -> StmClosureResult<T> {
+        match self.try_pop_front(transaction)? {
+            Some(value) => Ok(value),
+            None => retry(),
+        }
+    }
+
+    /// Remove the last element of the deque and return it.
+    ///
+    /// Calls `retry` if the deque is empty, blocking the transaction until an element is pushed.
+    pub fn pop_back(&self, transaction: &mut Transaction) -> StmClosureResult<T> {
+        match self.try_pop_back(transaction)? {
+            Some(value) => Ok(value),
+            None => retry(),
+        }
+    }
+
+    /// Remove the first element of the deque and return it, or `None` if the deque is empty.
+    pub fn try_pop_front(&self, transaction: &mut Transaction) -> StmClosureResult<Option<T>> {
+        Self::pop(transaction, &self.front, &self.back)
+    }
+
+    /// Remove the last element of the deque and return it, or `None` if the deque is empty.
+    pub fn try_pop_back(&self, transaction: &mut Transaction) -> StmClosureResult<Option<T>> {
+        Self::pop(transaction, &self.back, &self.front)
+    }
+
+    /// Return the first element of the deque, if any.
+    ///
+    /// This only reads the deque. If the front stack is empty, the element is found at the bottom
+    /// of the back stack, which takes time linear in the length of the deque.
+    pub fn front(&self, transaction: &mut Transaction) -> StmClosureResult<Option<T>> {
+        Self::peek(transaction, &self.front, &self.back)
+    }
+
+    /// Return the last element of the deque, if any.
+    ///
+    /// This only reads the deque. If the back stack is empty, the element is found at the bottom
+    /// of the front stack, which takes time linear in the length of the deque.
+    pub fn back(&self, transaction: &mut Transaction) -> StmClosureResult<Option<T>> {
+        Self::peek(transaction, &self.back, &self.front)
+    }
+
+    /// Return the number of elements of the deque.
+    ///
+    /// This reads both ends of the deque.
+    pub fn len(&self, transaction: &mut Transaction) -> StmClosureResult<usize> {
+        Ok(self.front.read(transaction)?.len + self.back.read(transaction)?.len)
+    }
+
+    /// Check if the deque is empty.
+    ///
+    /// This reads both ends of the deque.
+    pub fn is_empty(&self, transaction: &mut Transaction) -> StmClosureResult<bool> {
+        Ok(self.len(transaction)? == 0)
+    }
+
+    /// Return all elements of the deque, from front to back.
+    pub fn to_vec(&self, transaction: &mut Transaction) -> StmClosureResult<Vec<T>> {
+        let mut values = self.front.read(transaction)?.to_vec();
+        let mut back = self.back.read(transaction)?.to_vec();
+        back.reverse();
+        values.append(&mut back);
+        Ok(values)
+    }
+
+    /// Pop an element from `end`, refilling it from `other` if it is empty.
+    fn pop(
+        transaction: &mut Transaction,
+        end: &TVar<Stack<T>>,
+        other: &TVar<Stack<T>>,
+    ) -> StmClosureResult<Option<T>> {
+        let Some((value, rest)) = Self::refill(transaction, end, other)?.pop() else {
+            return Ok(None);
+        };
+        end.write(transaction, rest)?;
+        Ok(Some(value))
+    }
+
+    /// Return the element at `end` without moving any element between the stacks.
+    fn peek(
+        transaction: &mut Transaction,
+        end: &TVar<Stack<T>>,
+        other: &TVar<Stack<T>>,
+    ) -> StmClosureResult<Option<T>> {
+        if let Some(node) = &end.read_arc(transaction)?.top {
+            return Ok(Some(node.value.clone()));
+        }
+        Ok(other.read_arc(transaction)?.bottom().cloned())
+    }
+
+    /// Return the stack of `end`. If it is empty, move the bottom half of `other` to it first.
+    fn refill(
+        transaction: &mut Transaction,
+        end: &TVar<Stack<T>>,
+        other: &TVar<Stack<T>>,
+    ) -> StmClosureResult<Stack<T>> {
+        let stack = end.read(transaction)?;
+        if stack.len > 0 {
+            return Ok(stack);
+        }
+        let values = other.read(transaction)?.to_vec();
+        if values.is_empty() {
+            return Ok(stack);
+        }
+
+        // `values` goes from the top of `other` to its bottom, which is the top of `end`.
+        let (kept, moved) = values.split_at(values.len() / 2);
+        other.write(transaction, Stack::from_bottom(kept.iter().rev().cloned()))?;
+        let stack = Stack::from_bottom(moved.iter().cloned());
+        end.write(transaction, stack.clone())?;
+        Ok(stack)
+    }
+}
+
+impl<T> Default for TDeque<T>
+where
+    T: Any + Send + Sync + Clone,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Clone for TDeque<T> {
+    fn clone(&self) -> Self {
+        Self {
+            front: self.front.clone(),
+            back: self.back.clone(),
+        }
+    }
+}
+
+/// Debug output the deque.
+impl<T> Debug for TDeque<T>
+where
+    T: Any + Send + Sync + Clone + Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        let front = self.front.read_atomic().to_vec();
+        let back = self.back.read_atomic().to_vec();
+        f.debug_list()
+            .entries(front.iter().chain(back.iter().rev()))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::atomically;
+
+    #[test]
+    fn both_ends() {
+        let deque = TDeque::new();
+
+        atomically(|tx| {
+            for i in 0..5 {
+                deque.push_back(tx, i)?;
+            }
+            deque.push_front(tx, -1)
+        });
+        assert_eq!(atomically(|tx| deque.len(tx)), 6);
+        assert_eq!(atomically(|tx| deque.to_vec(tx)), [-1, 0, 1, 2, 3, 4]);
+
+        assert_eq!(atomically(|tx| deque.pop_front(tx)), -1);
+        // The front stack is empty, the first element is at the bottom of the back stack.
+        assert_eq!(atomically(|tx| deque.front(tx)), Some(0));
+        // Popping it moves half of the back stack over.
+        assert_eq!(atomically(|tx| deque.pop_front(tx)), 0);
+        assert_eq!(atomically(|tx| deque.pop_back(tx)), 4);
+        assert_eq!(atomically(|tx| deque.to_vec(tx)), [1, 2, 3]);
+
+        let popped: Vec<_> = (0..3)
+            .map(|_| atomically(|tx| deque.pop_back(tx)))
+            .collect();
+        assert_eq!(popped, [3, 2, 1]);
+        assert!(atomically(|tx| deque.is_empty(tx)));
+        assert_eq!(atomically(|tx| deque.try_pop_front(tx)), None);
+    }
+
+    /// Operations on opposite ends should not conflict.
+    #[test]
+    fn opposite_ends_no_conflict() {
+        let deque = TDeque::new();
+        atomically(|tx| deque.push_front(tx, 0));
+
+        let mut tx1 = Transaction::default();
+        let mut tx2 = Transaction::default();
+        deque.push_back(&mut tx1, 1).unwrap();
+        assert_eq!(deque.pop_front(&mut tx2).unwrap(), 0);
+        assert!(tx2.commit());
+        assert!(tx1.commit());
+
+        assert_eq!(atomically(|tx| deque.to_vec(tx)), [1]);
+    }
+
+    /// Peeking at an empty end does not move elements, so it does not conflict with pushers.
+    #[test]
+    fn peek_is_read_only() {
+        let deque = TDeque::new();
+        atomically(|tx| {
+            deque.push_back(tx, 1)?;
+            deque.push_back(tx, 2)
+        });
+
+        let mut tx1 = Transaction::default();
+        let mut tx2 = Transaction::default();
+        assert_eq!(deque.front(&mut tx1).unwrap(), Some(1));
+        assert_eq!(deque.back(&mut tx1).unwrap(), Some(2));
+        deque.push_back(&mut tx2, 3).unwrap();
+        assert!(tx1.commit());
+        assert!(tx2.commit());
+
+        assert_eq!(deque.front.read_atomic().len, 0);
+        assert_eq!(atomically(|tx| deque.to_vec(tx)), [1, 2, 3]);
+    }
+
+    /// Popping from an empty deque blocks until an element is pushed.
+    #[test]
+    fn threaded_pop() {
+        let deque = TDeque::new();
+        let dequec = deque.clone();
+
+        let x = crate::test::async_test(
+            800,
+            move || atomically(|tx| dequec.pop_front(tx)),
+            || {
+                std::thread::sleep(std::time::Duration::from_millis(100));
+                atomically(|tx| deque.push_back(tx, 42));
+            },
+        );
+
+        assert_eq!(x, Some(42));
+    }
+
+    #[test]
+    fn drop_long_deque() {
+        let deque = TDeque::new();
+        atomically(|tx| {
+            for i in 0..100_000 {
+                deque.push_back(tx, i)?;
+            }
+            Ok(())
+        });
+        drop(deque);
+    }
+}
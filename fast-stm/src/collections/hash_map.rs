@@ -0,0 +1,379 @@
+use std::any::Any;
+use std::borrow::Borrow;
+use std::collections::hash_map::RandomState;
+use std::fmt::{self, Debug};
+use std::hash::{BuildHasher, Hash};
+use std::sync::Arc;
+
+use crate::{StmClosureResult, TVar, Transaction};
+
+/// Number of buckets used by [`THashMap::new`].
+const DEFAULT_BUCKETS: usize = 64;
+
+/// Content of a single bucket.
+type Bucket<K, V> = Vec<(K, V)>;
+
+/// A transactional hash map.
+///
+/// Keys are striped across a fixed number of buckets, each bucket being stored in its own
+/// `TVar`. Two transactions accessing keys that land in different buckets do not conflict,
+/// and a modification only clones the content of a single bucket.
+///
+/// The number of buckets is set at creation and never changes. Lookups are linear in the size
+/// of a bucket, so it should be chosen according to the expected number of entries.
+///
+/// Operations spanning the whole map (`len`, `to_vec`, ...) read every bucket, and therefore
+/// conflict with any concurrent modification of the map.
+///
+/// Cloning a `THashMap` yields a new handle to the same map, like `TVar`.
+///
+/// ```
+/// # use fast_stm::*;
+/// let map = THashMap::new();
+///
+/// atomically(|trans| {
+///     map.insert(trans, "alice", 42)?;
+///     map.update(trans, "alice", |v| v.map(|x| x + 1))
+/// });
+///
+/// assert_eq!(atomically(|trans| map.get(trans, "alice")), Some(43));
+/// ```
+pub struct THashMap<K, V, S = RandomState> {
+    buckets: Arc<[TVar<Bucket<K, V>>]>,
+    hasher: S,
+}
+
+impl<K, V> THashMap<K, V, RandomState>
+where
+    K: Any + Send + Sync + Clone + Hash + Eq,
+    V: Any + Send + Sync + Clone,
+{
+    /// Create an empty map with a default number of buckets.
+    pub fn new() -> Self {
+        Self::with_buckets(DEFAULT_BUCKETS)
+    }
+
+    /// Create an empty map with `n_buckets` buckets.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n_buckets` is zero.
+    pub fn with_buckets(n_buckets: usize) -> Self {
+        Self::with_buckets_and_hasher(n_buckets, RandomState::new())
+    }
+}
+
+impl<K, V> Default for THashMap<K, V, RandomState>
+where
+    K: Any + Send + Sync + Clone + Hash + Eq,
+    V: Any + Send + Sync + Clone,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V, S> THashMap<K, V, S>
+where
+    K: Any + Send + Sync + Clone + Hash + Eq,
+    V: Any + Send + Sync + Clone,
+    S: BuildHasher,
+{
+    /// Create an empty map with `n_buckets` buckets, using `hasher` to hash keys.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n_buckets` is zero.
+    pub fn with_buckets_and_hasher(n_buckets: usize, hasher: S) -> Self {
+        assert!(n_buckets > 0, "THashMap needs at least one bucket");
+        Self {
+            buckets: (0..n_buckets).map(|_| TVar::new(Vec::new())).collect(),
+            hasher,
+        }
+    }
+
+    /// Return the number of buckets of the map.
+    pub fn n_buckets(&self) -> usize {
+        self.buckets.len()
+    }
+
+    /// Return the value associated to `key`, if any.
+    pub fn get<Q>(&self, transaction: &mut Transaction, key: &Q) -> StmClosureResult<Option<V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let bucket = self.bucket(key).read_arc(transaction)?;
+        Ok(bucket
+            .iter()
+            .find_map(|(k, v)| (k.borrow() == key).then(|| v.clone())))
+    }
+
+    /// Check if the map contains a value for `key`.
+    pub fn contains_key<Q>(&self, transaction: &mut Transaction, key: &Q) -> StmClosureResult<bool>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let bucket = self.bucket(key).read_arc(transaction)?;
+        Ok(bucket.iter().any(|(k, _)| k.borrow() == key))
+    }
+
+    /// Insert a key-value pair into the map, returning the previous value of `key`, if any.
+    pub fn insert(
+        &self,
+        transaction: &mut Transaction,
+        key: K,
+        value: V,
+    ) -> StmClosureResult<Option<V>> {
+        let mut old = None;
+        self.bucket(&key).modify(transaction, |mut bucket| {
+            match bucket.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, v)) => old = Some(std::mem::replace(v, value)),
+                None => bucket.push((key, value)),
+            }
+            bucket
+        })?;
+        Ok(old)
+    }
+
+    /// Remove `key` from the map, returning its value, if any.
+    ///
+    /// The bucket of `key` is only written if the key was present.
+    pub fn remove<Q>(&self, transaction: &mut Transaction, key: &Q) -> StmClosureResult<Option<V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let var = self.bucket(key);
+        let mut bucket = var.read(transaction)?;
+        match bucket.iter().position(|(k, _)| k.borrow() == key) {
+            Some(idx) => {
+                let (_, v) = bucket.swap_remove(idx);
+                var.write(transaction, bucket)?;
+                Ok(Some(v))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Update the entry of `key` using `f`, returning the previous value, if any.
+    ///
+    /// `f` receives the current value of `key`, if any. The entry is set to the returned value,
+    /// or removed from the map if `f` returns `None`. The bucket of `key` is not written if the
+    /// key is absent both before and after the update.
+    ///
+    /// ```
+    /// # use fast_stm::*;
+    /// let counts = THashMap::new();
+    ///
+    /// atomically(|trans| {
+    ///     for word in ["a", "b", "a"] {
+    ///         counts.update(trans, word, |c| Some(c.unwrap_or(0) + 1))?;
+    ///     }
+    ///     Ok(())
+    /// });
+    ///
+    /// assert_eq!(atomically(|trans| counts.get(trans, "a")), Some(2));
+    /// ```
+    pub fn update<F>(
+        &self,
+        transaction: &mut Transaction,
+        key: K,
+        f: F,
+    ) -> StmClosureResult<Option<V>>
+    where
+        F: FnOnce(Option<V>) -> Option<V>,
+    {
+        let var = self.bucket(&key);
+        let mut bucket = var.read(transaction)?;
+        let idx = bucket.iter().position(|(k, _)| *k == key);
+        let old = idx.map(|idx| bucket[idx].1.clone());
+        match (idx, f(old.clone())) {
+            (Some(idx), Some(new)) => bucket[idx].1 = new,
+            (Some(idx), None) => {
+                bucket.swap_remove(idx);
+            }
+            (None, Some(new)) => bucket.push((key, new)),
+            (None, None) => return Ok(None),
+        }
+        var.write(transaction, bucket)?;
+        Ok(old)
+    }
+
+    /// Return the number of entries of the map.
+    ///
+    /// This reads every bucket of the map.
+    pub fn len(&self, transaction: &mut Transaction) -> StmClosureResult<usize> {
+        let mut len = 0;
+        for var in self.buckets.iter() {
+            len += var.read_arc(transaction)?.len();
+        }
+        Ok(len)
+    }
+
+    /// Check if the map is empty.
+    ///
+    /// This reads every bucket of the map.
+    pub fn is_empty(&self, transaction: &mut Transaction) -> StmClosureResult<bool> {
+        for var in self.buckets.iter() {
+            if !var.read_arc(transaction)?.is_empty() {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Return all entries of the map, in an arbitrary order.
+    ///
+    /// This reads every bucket of the map, so the returned entries are consistent within the
+    /// transaction.
+    pub fn to_vec(&self, transaction: &mut Transaction) -> StmClosureResult<Vec<(K, V)>> {
+        let mut entries = Vec::new();
+        for var in self.buckets.iter() {
+            entries.extend(var.read(transaction)?);
+        }
+        Ok(entries)
+    }
+
+    /// Remove all entries of the map.
+    ///
+    /// Only non-empty buckets are written.
+    pub fn clear(&self, transaction: &mut Transaction) -> StmClosureResult<()> {
+        for var in self.buckets.iter() {
+            if !var.read_arc(transaction)?.is_empty() {
+                var.write(transaction, Vec::new())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Return the bucket `key` belongs to.
+    fn bucket<Q: Hash + ?Sized>(&self, key: &Q) -> &TVar<Bucket<K, V>> {
+        let n_buckets = self.buckets.len() as u64;
+        let idx = self.hasher.hash_one(key) % n_buckets;
+        #[allow(clippy::cast_possible_truncation)]
+        &self.buckets[idx as usize]
+    }
+}
+
+impl<K, V, S: Clone> Clone for THashMap<K, V, S> {
+    fn clone(&self) -> Self {
+        Self {
+            buckets: self.buckets.clone(),
+            hasher: self.hasher.clone(),
+        }
+    }
+}
+
+/// Debug output the map.
+impl<K, V, S> Debug for THashMap<K, V, S>
+where
+    K: Any + Send + Sync + Clone + Debug,
+    V: Any + Send + Sync + Clone + Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        f.debug_map()
+            .entries(self.buckets.iter().flat_map(TVar::read_atomic))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::atomically;
+
+    #[test]
+    fn insert_get_remove() {
+        let map = THashMap::new();
+
+        let old = atomically(|tx| {
+            map.insert(tx, 1, "one")?;
+            map.insert(tx, 2, "two")?;
+            map.insert(tx, 1, "uno")
+        });
+        assert_eq!(old, Some("one"));
+
+        assert_eq!(atomically(|tx| map.get(tx, &1)), Some("uno"));
+        assert_eq!(atomically(|tx| map.remove(tx, &1)), Some("uno"));
+        assert_eq!(atomically(|tx| map.remove(tx, &1)), None);
+        assert!(!atomically(|tx| map.contains_key(tx, &1)));
+        assert!(atomically(|tx| map.contains_key(tx, &2)));
+    }
+
+    #[test]
+    fn update_entry() {
+        let map = THashMap::with_buckets(4);
+
+        atomically(|tx| map.update(tx, 'a', |v| Some(v.unwrap_or(0) + 1)));
+        atomically(|tx| map.update(tx, 'a', |v| Some(v.unwrap_or(0) + 1)));
+        assert_eq!(atomically(|tx| map.get(tx, &'a')), Some(2));
+
+        // returning `None` removes the entry
+        let old = atomically(|tx| map.update(tx, 'a', |_| None));
+        assert_eq!(old, Some(2));
+        assert!(atomically(|tx| map.is_empty(tx)));
+    }
+
+    #[test]
+    fn len_and_iteration() {
+        let map = THashMap::with_buckets(3);
+
+        atomically(|tx| {
+            for i in 0..10 {
+                map.insert(tx, i, i * i)?;
+            }
+            Ok(())
+        });
+
+        let (len, mut entries) = atomically(|tx| Ok((map.len(tx)?, map.to_vec(tx)?)));
+        entries.sort_unstable();
+        assert_eq!(len, 10);
+        assert_eq!(entries, (0..10).map(|i| (i, i * i)).collect::<Vec<_>>());
+
+        atomically(|tx| map.clear(tx));
+        assert_eq!(atomically(|tx| map.len(tx)), 0);
+    }
+
+    /// Transactions writing keys of different buckets should both commit.
+    #[test]
+    fn disjoint_buckets_do_not_conflict() {
+        let map = THashMap::with_buckets(16);
+        // find two keys that are stored in different buckets
+        let k1 = 0;
+        let k2 = (1..1000)
+            .find(|k| !TVar::ref_eq(map.bucket(&k1), map.bucket(k)))
+            .unwrap();
+
+        let mut tx1 = Transaction::default();
+        let mut tx2 = Transaction::default();
+        map.insert(&mut tx1, k1, 1).unwrap();
+        map.insert(&mut tx2, k2, 2).unwrap();
+
+        assert!(tx2.commit());
+        assert!(tx1.commit());
+        assert_eq!(atomically(|tx| map.len(tx)), 2);
+    }
+
+    #[test]
+    fn threaded_inserts() {
+        let map = THashMap::new();
+
+        let handles: Vec<_> = (0..4)
+            .map(|t| {
+                let map = map.clone();
+                std::thread::spawn(move || {
+                    for i in 0..100 {
+                        atomically(|tx| map.insert(tx, t * 100 + i, t));
+                    }
+                })
+            })
+            .collect();
+        for h in handles {
+            h.join().unwrap();
+        }
+
+        assert_eq!(atomically(|tx| map.len(tx)), 400);
+    }
+}
@@ -0,0 +1,334 @@
+use std::any::Any;
+use std::fmt::{self, Debug};
+use std::sync::Arc;
+
+use super::take_link;
+use crate::{StmClosureResult, TVar, Transaction};
+
+/// Link to the next node of the list.
+pub(super) type Link<T> = Option<Arc<Node<T>>>;
+
+pub(super) struct Node<T>
+where
+    T: Any + Send + Sync,
+{
+    pub(super) value: T,
+    pub(super) next: TVar<Link<T>>,
+}
+
+impl<T> Node<T>
+where
+    T: Any + Send + Sync,
+{
+    pub(super) fn new(value: T, next: Link<T>) -> Arc<Self> {
+        Arc::new(Self {
+            value,
+            next: TVar::new(next),
+        })
+    }
+}
+
+impl<T> Drop for Node<T>
+where
+    T: Any + Send + Sync,
+{
+    fn drop(&mut self) {
+        // Dropping a node drops its successor if this was the last reference to it, which
+        // recurses through the whole list. Unlink the chain iteratively instead.
+        let mut link = take_link(&self.next);
+        while let Some(node) = link {
+            match Arc::try_unwrap(node) {
+                Ok(node) => link = take_link(&node.next),
+                Err(_) => break,
+            }
+        }
+    }
+}
+
+/// Transactional iterator over the values of a [`TList`] or a [`TSet`][super::TSet].
+///
+/// Each call to `next` reads one link of the list. Iteration stops after the first error,
+/// which should be propagated to abort the transaction.
+pub struct ListIter<'a, T>
+where
+    T: Any + Send + Sync,
+{
+    transaction: &'a mut Transaction,
+    /// The link to read next, `None` once iteration is over.
+    next: Option<TVar<Link<T>>>,
+}
+
+impl<'a, T> ListIter<'a, T>
+where
+    T: Any + Send + Sync,
+{
+    pub(super) fn new(transaction: &'a mut Transaction, head: &TVar<Link<T>>) -> Self {
+        Self {
+            transaction,
+            next: Some(head.clone()),
+        }
+    }
+}
+
+impl<T> Iterator for ListIter<'_, T>
+where
+    T: Any + Send + Sync + Clone,
+{
+    type Item = StmClosureResult<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let var = self.next.take()?;
+        match var.read(self.transaction) {
+            Ok(Some(node)) => {
+                self.next = Some(node.next.clone());
+                Some(Ok(node.value.clone()))
+            }
+            Ok(None) => None,
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+/// A transactional singly linked list.
+///
+/// Each link of the list is stored in its own `TVar`. A transaction only reads the links
+/// it traverses, and only writes the links surrounding the nodes it inserts or removes, so
+/// transactions modifying the front of the list do not conflict with transactions reading
+/// further down the list, unless the nodes they read are removed.
+///
+/// Cloning a `TList` yields a new handle to the same list, like `TVar`.
+///
+/// ```
+/// # use fast_stm::*;
+/// let list = TList::new();
+///
+/// atomically(|trans| {
+///     list.push_front(trans, 2)?;
+///     list.push_front(trans, 1)?;
+///     list.push_back(trans, 3)
+/// });
+///
+/// let sum = atomically(|trans| list.iter(trans).sum::<StmClosureResult<i32>>());
+/// assert_eq!(sum, 6);
+/// ```
+pub struct TList<T>
+where
+    T: Any + Send + Sync,
+{
+    head: TVar<Link<T>>,
+}
+
+impl<T> TList<T>
+where
+    T: Any + Send + Sync + Clone,
+{
+    /// Create an empty list.
+    pub fn new() -> Self {
+        Self {
+            head: TVar::new(None),
+        }
+    }
+
+    /// Push an element at the front of the list.
+    pub fn push_front(&self, transaction: &mut Transaction, value: T) -> StmClosureResult<()> {
+        let head = self.head.read(transaction)?;
+        self.head.write(transaction, Some(Node::new(value, head)))
+    }
+
+    /// Push an element at the back of the list.
+    ///
+    /// This reads every link of the list.
+    pub fn push_back(&self, transaction: &mut Transaction, value: T) -> StmClosureResult<()> {
+        let mut last = self.head.clone();
+        while let Some(node) = last.read(transaction)? {
+            last = node.next.clone();
+        }
+        last.write(transaction, Some(Node::new(value, None)))
+    }
+
+    /// Remove the first element of the list and return it, or `None` if the list is empty.
+    pub fn pop_front(&self, transaction: &mut Transaction) -> StmClosureResult<Option<T>> {
+        let Some(node) = self.head.read(transaction)? else {
+            return Ok(None);
+        };
+        let next = node.next.read(transaction)?;
+        self.head.write(transaction, next)?;
+        Ok(Some(node.value.clone()))
+    }
+
+    /// Return the first element of the list, if any.
+    pub fn front(&self, transaction: &mut Transaction) -> StmClosureResult<Option<T>> {
+        Ok(self.head.read(transaction)?.map(|node| node.value.clone()))
+    }
+
+    /// Check if the list contains `value`.
+    ///
+    /// This reads the links up to the first occurrence of `value`.
+    pub fn contains(&self, transaction: &mut Transaction, value: &T) -> StmClosureResult<bool>
+    where
+        T: PartialEq,
+    {
+        for v in self.iter(transaction) {
+            if v? == *value {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Remove the first occurrence of `value` from the list.
+    ///
+    /// Return `true` if the value was present.
+    pub fn remove(&self, transaction: &mut Transaction, value: &T) -> StmClosureResult<bool>
+    where
+        T: PartialEq,
+    {
+        let mut pred = self.head.clone();
+        while let Some(node) = pred.read(transaction)? {
+            if node.value == *value {
+                let next = node.next.read(transaction)?;
+                pred.write(transaction, next)?;
+                return Ok(true);
+            }
+            pred = node.next.clone();
+        }
+        Ok(false)
+    }
+
+    /// Iterate over the elements of the list, from front to back.
+    pub fn iter<'a>(&self, transaction: &'a mut Transaction) -> ListIter<'a, T> {
+        ListIter::new(transaction, &self.head)
+    }
+
+    /// Return the number of elements of the list.
+    ///
+    /// This reads every link of the list.
+    pub fn len(&self, transaction: &mut Transaction) -> StmClosureResult<usize> {
+        self.iter(transaction)
+            .try_fold(0, |len, value| value.map(|_| len + 1))
+    }
+
+    /// Check if the list is empty.
+    pub fn is_empty(&self, transaction: &mut Transaction) -> StmClosureResult<bool> {
+        Ok(self.head.read(transaction)?.is_none())
+    }
+
+    /// Return all elements of the list, from front to back.
+    pub fn to_vec(&self, transaction: &mut Transaction) -> StmClosureResult<Vec<T>> {
+        self.iter(transaction).collect()
+    }
+}
+
+impl<T> Default for TList<T>
+where
+    T: Any + Send + Sync + Clone,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Clone for TList<T>
+where
+    T: Any + Send + Sync,
+{
+    fn clone(&self) -> Self {
+        Self {
+            head: self.head.clone(),
+        }
+    }
+}
+
+/// Debug output the list.
+impl<T> Debug for TList<T>
+where
+    T: Any + Send + Sync + Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        let mut list = f.debug_list();
+        let mut link = self.head.read_atomic();
+        while let Some(node) = link {
+            list.entry(&node.value);
+            link = node.next.read_atomic();
+        }
+        list.finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::atomically;
+
+    #[test]
+    fn push_pop() {
+        let list = TList::new();
+
+        atomically(|tx| {
+            list.push_back(tx, 2)?;
+            list.push_front(tx, 1)?;
+            list.push_back(tx, 3)
+        });
+        assert_eq!(atomically(|tx| list.to_vec(tx)), [1, 2, 3]);
+        assert_eq!(atomically(|tx| list.len(tx)), 3);
+        assert_eq!(atomically(|tx| list.front(tx)), Some(1));
+
+        assert_eq!(atomically(|tx| list.pop_front(tx)), Some(1));
+        assert!(atomically(|tx| list.remove(tx, &3)));
+        assert!(!atomically(|tx| list.remove(tx, &3)));
+        assert!(atomically(|tx| list.contains(tx, &2)));
+        assert_eq!(atomically(|tx| list.pop_front(tx)), Some(2));
+        assert!(atomically(|tx| list.is_empty(tx)));
+    }
+
+    /// Removing a node conflicts with an insertion right after it.
+    #[test]
+    fn remove_insert_conflict() {
+        let list = TList::new();
+        atomically(|tx| list.push_front(tx, 1));
+
+        let mut tx1 = Transaction::default();
+        let mut tx2 = Transaction::default();
+        list.push_back(&mut tx1, 2).unwrap();
+        assert!(list.remove(&mut tx2, &1).unwrap());
+        assert!(tx2.commit());
+        assert!(!tx1.commit());
+
+        assert!(atomically(|tx| list.is_empty(tx)));
+    }
+
+    #[test]
+    fn threaded_push_pop() {
+        let list = TList::new();
+
+        let handles: Vec<_> = (0..4)
+            .map(|t| {
+                let list = list.clone();
+                std::thread::spawn(move || {
+                    for i in 0..100 {
+                        atomically(|tx| list.push_front(tx, t * 100 + i));
+                    }
+                    (0..50)
+                        .filter(|_| atomically(|tx| list.pop_front(tx)).is_some())
+                        .count()
+                })
+            })
+            .collect();
+        let popped: usize = handles.into_iter().map(|h| h.join().unwrap()).sum();
+
+        assert_eq!(popped, 200);
+        assert_eq!(atomically(|tx| list.len(tx)), 200);
+    }
+
+    #[test]
+    fn drop_long_list() {
+        let list = TList::new();
+        atomically(|tx| {
+            for i in 0..100_000 {
+                list.push_front(tx, i)?;
+            }
+            Ok(())
+        });
+        drop(list);
+    }
+}
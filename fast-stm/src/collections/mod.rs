@@ -0,0 +1,47 @@
+//! Transactional data structures built on top of [`TVar`][crate::TVar].
+//!
+//! Wrapping a whole collection into a single `TVar` makes every access to the collection
+//! conflict with every other one, and clones the collection on each modification. The
+//! structures of this module split their state across multiple `TVar`s, so that transactions
+//! touching unrelated parts of a collection do not conflict.
+//!
+//! Like the one of `TVar`, the `Debug` implementations of the collections read their variables
+//! one after the other without starting a transaction, so the printed state may be inconsistent
+//! if another thread modifies the collection at the same time.
+
+mod counter;
+mod deque;
+mod hash_map;
+mod list;
+mod priority_queue;
+mod ring_buffer;
+mod set;
+mod skip_list;
+
+pub use counter::TCounter;
+pub use deque::TDeque;
+pub use hash_map::THashMap;
+pub use list::{ListIter, TList};
+pub use priority_queue::TPriorityQueue;
+pub use ring_buffer::TRingBuffer;
+pub use set::TSet;
+pub use skip_list::TSkipList;
+
+use std::any::Any;
+use std::sync::Arc;
+
+use crate::TVar;
+
+/// Take the value of a link between nodes that is not reachable anymore, leaving `None` in its
+/// place.
+///
+/// Nodes use it to unlink their successors iteratively when dropped, instead of recursively.
+fn take_link<N>(var: &TVar<Option<Arc<N>>>) -> Option<Arc<N>>
+where
+    N: Any + Send + Sync,
+{
+    let mut value = var.control_block().value.write();
+    let link = Option::clone(&value);
+    *value = Arc::new(None);
+    link
+}
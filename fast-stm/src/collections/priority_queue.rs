@@ -0,0 +1,396 @@
+use std::any::Any;
+use std::fmt::{self, Debug};
+use std::sync::Arc;
+
+use crate::{retry, StmClosureResult, TVar, Transaction};
+
+/// Link to a subtree of the heap.
+type Link<T> = Option<Arc<Node<T>>>;
+
+struct Node<T>
+where
+    T: Any + Send + Sync,
+{
+    value: T,
+    /// The children are stored in a single `TVar`, along with the rank of the node.
+    links: TVar<Links<T>>,
+}
+
+struct Links<T>
+where
+    T: Any + Send + Sync,
+{
+    left: Link<T>,
+    right: Link<T>,
+    /// Length of the right spine of the node.
+    ///
+    /// The rank of the left child is never smaller than the rank of the right one, so that
+    /// the right spine stays logarithmic in the size of the subtree.
+    rank: usize,
+}
+
+impl<T> Clone for Links<T>
+where
+    T: Any + Send + Sync,
+{
+    fn clone(&self) -> Self {
+        Self {
+            left: self.left.clone(),
+            right: self.right.clone(),
+            rank: self.rank,
+        }
+    }
+}
+
+impl<T> Links<T>
+where
+    T: Any + Send + Sync,
+{
+    fn leaf() -> Self {
+        Self {
+            left: None,
+            right: None,
+            rank: 1,
+        }
+    }
+}
+
+impl<T> Drop for Node<T>
+where
+    T: Any + Send + Sync,
+{
+    fn drop(&mut self) {
+        // The left spine of the heap may be as long as the heap itself, so dropping the
+        // subtrees recursively could overflow the stack. Unlink them iteratively instead.
+        let mut stack = take_links(&self.links);
+        while let Some(node) = stack.pop() {
+            if let Ok(node) = Arc::try_unwrap(node) {
+                stack.extend(take_links(&node.links));
+            }
+        }
+    }
+}
+
+/// Take the children of a node that is not reachable anymore.
+fn take_links<T>(var: &TVar<Links<T>>) -> Vec<Arc<Node<T>>>
+where
+    T: Any + Send + Sync,
+{
+    let mut value = var.control_block().value.write();
+    let links = Links::clone(&value);
+    *value = Arc::new(Links::<T>::leaf());
+    [links.left, links.right].into_iter().flatten().collect()
+}
+
+/// A transactional priority queue, implemented as a leftist heap.
+///
+/// Like `BinaryHeap`, this is a max-heap: `pop` returns the greatest element. Wrap elements
+/// in `std::cmp::Reverse` to get a min-heap.
+///
+/// The children of each node are stored in their own `TVar`. Pushing or popping an element
+/// rewrites the links along the right spines of the merged subtrees, which are logarithmic in
+/// the size of the heap, and shares all other nodes with the previous version.
+///
+/// Every `push` and `pop` goes through the root of the heap, so concurrent modifications of
+/// the same queue conflict with each other. Reading the greatest element with `peek` only
+/// conflicts with modifications that change it.
+///
+/// Cloning a `TPriorityQueue` yields a new handle to the same queue, like `TVar`.
+///
+/// ```
+/// # use fast_stm::*;
+/// let jobs = TPriorityQueue::new();
+///
+/// atomically(|trans| {
+///     jobs.push(trans, (1, "low"))?;
+///     jobs.push(trans, (3, "high"))?;
+///     jobs.push(trans, (2, "medium"))
+/// });
+///
+/// assert_eq!(atomically(|trans| jobs.pop(trans)), (3, "high"));
+/// assert_eq!(atomically(|trans| jobs.peek(trans)), Some((2, "medium")));
+/// ```
+pub struct TPriorityQueue<T>
+where
+    T: Any + Send + Sync,
+{
+    root: TVar<Link<T>>,
+}
+
+impl<T> TPriorityQueue<T>
+where
+    T: Any + Send + Sync + Clone + Ord,
+{
+    /// Create an empty queue.
+    pub fn new() -> Self {
+        Self {
+            root: TVar::new(None),
+        }
+    }
+
+    /// Push an element into the queue.
+    pub fn push(&self, transaction: &mut Transaction, value: T) -> StmClosureResult<()> {
+        let node = Arc::new(Node {
+            value,
+            links: TVar::new(Links::leaf()),
+        });
+        let root = self.root.read(transaction)?;
+        let root = Self::merge(transaction, root, Some(node))?;
+        self.root.write(transaction, root)
+    }
+
+    /// Remove the greatest element of the queue and return it.
+    ///
+    /// Calls `retry` if the queue is empty, blocking the transaction until an element is pushed.
+    pub fn pop(&self, transaction: &mut Transaction) -> StmClosureResult<T> {
+        match self.try_pop(transaction)? {
+            Some(value) => Ok(value),
+            None => retry(),
+        }
+    }
+
+    /// Remove the greatest element of the queue and return it, or `None` if the queue is empty.
+    pub fn try_pop(&self, transaction: &mut Transaction) -> StmClosureResult<Option<T>> {
+        let Some(node) = self.root.read(transaction)? else {
+            return Ok(None);
+        };
+        let links = node.links.read(transaction)?;
+        let root = Self::merge(transaction, links.left, links.right)?;
+        self.root.write(transaction, root)?;
+        Ok(Some(node.value.clone()))
+    }
+
+    /// Return the greatest element of the queue, if any.
+    pub fn peek(&self, transaction: &mut Transaction) -> StmClosureResult<Option<T>> {
+        Ok(self.root.read(transaction)?.map(|node| node.value.clone()))
+    }
+
+    /// Remove all elements matching the predicate `f`, and return them in no particular order.
+    ///
+    /// This reads every node of the heap, and rebuilds it if any element is removed.
+    pub fn remove_if<F>(&self, transaction: &mut Transaction, mut f: F) -> StmClosureResult<Vec<T>>
+    where
+        F: FnMut(&T) -> bool,
+    {
+        let (removed, kept): (Vec<_>, Vec<_>) = self
+            .nodes(transaction)?
+            .into_iter()
+            .partition(|node| f(&node.value));
+        if removed.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // Merge the remaining nodes pairwise, which builds the heap in linear time.
+        let mut heaps = std::collections::VecDeque::with_capacity(kept.len());
+        for node in kept {
+            node.links.write(transaction, Links::leaf())?;
+            heaps.push_back(Some(node));
+        }
+        let root = loop {
+            match (heaps.pop_front(), heaps.pop_front()) {
+                (Some(a), Some(b)) => heaps.push_back(Self::merge(transaction, a, b)?),
+                (a, _) => break a.flatten(),
+            }
+        };
+        self.root.write(transaction, root)?;
+
+        Ok(removed.into_iter().map(|node| node.value.clone()).collect())
+    }
+
+    /// Return the number of elements of the queue.
+    ///
+    /// This reads every node of the heap.
+    pub fn len(&self, transaction: &mut Transaction) -> StmClosureResult<usize> {
+        Ok(self.nodes(transaction)?.len())
+    }
+
+    /// Check if the queue is empty.
+    pub fn is_empty(&self, transaction: &mut Transaction) -> StmClosureResult<bool> {
+        Ok(self.root.read(transaction)?.is_none())
+    }
+
+    /// Return all elements of the queue, in ascending order.
+    pub fn to_sorted_vec(&self, transaction: &mut Transaction) -> StmClosureResult<Vec<T>> {
+        let mut values: Vec<_> = self
+            .nodes(transaction)?
+            .iter()
+            .map(|node| node.value.clone())
+            .collect();
+        values.sort();
+        Ok(values)
+    }
+
+    /// Return all nodes of the heap.
+    fn nodes(&self, transaction: &mut Transaction) -> StmClosureResult<Vec<Arc<Node<T>>>> {
+        let mut nodes = Vec::new();
+        let mut stack: Vec<_> = self.root.read(transaction)?.into_iter().collect();
+        while let Some(node) = stack.pop() {
+            let links = node.links.read(transaction)?;
+            stack.extend(links.left);
+            stack.extend(links.right);
+            nodes.push(node);
+        }
+        Ok(nodes)
+    }
+
+    /// Merge two heaps along their right spines.
+    fn merge(transaction: &mut Transaction, a: Link<T>, b: Link<T>) -> StmClosureResult<Link<T>> {
+        let (a, b) = match (a, b) {
+            (None, h) | (h, None) => return Ok(h),
+            (Some(a), Some(b)) if a.value < b.value => (b, a),
+            (Some(a), Some(b)) => (a, b),
+        };
+
+        let links = a.links.read(transaction)?;
+        let right = Self::merge(transaction, links.right, Some(b))?;
+        let left = links.left;
+        let left_rank = Self::rank(transaction, left.as_ref())?;
+        let right_rank = Self::rank(transaction, right.as_ref())?;
+
+        let links = if left_rank < right_rank {
+            Links {
+                left: right,
+                right: left,
+                rank: left_rank + 1,
+            }
+        } else {
+            Links {
+                left,
+                right,
+                rank: right_rank + 1,
+            }
+        };
+        a.links.write(transaction, links)?;
+        Ok(Some(a))
+    }
+
+    fn rank(transaction: &mut Transaction, link: Option<&Arc<Node<T>>>) -> StmClosureResult<usize> {
+        match link {
+            Some(node) => Ok(node.links.read(transaction)?.rank),
+            None => Ok(0),
+        }
+    }
+}
+
+impl<T> Default for TPriorityQueue<T>
+where
+    T: Any + Send + Sync + Clone + Ord,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Clone for TPriorityQueue<T>
+where
+    T: Any + Send + Sync,
+{
+    fn clone(&self) -> Self {
+        Self {
+            root: self.root.clone(),
+        }
+    }
+}
+
+/// Debug output the queue, in no particular order.
+impl<T> Debug for TPriorityQueue<T>
+where
+    T: Any + Send + Sync + Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        let mut list = f.debug_list();
+        let mut stack: Vec<_> = self.root.read_atomic().into_iter().collect();
+        while let Some(node) = stack.pop() {
+            let links = node.links.read_atomic();
+            stack.extend(links.left);
+            stack.extend(links.right);
+            list.entry(&node.value);
+        }
+        list.finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::atomically;
+
+    fn filled(values: impl IntoIterator<Item = i32>) -> TPriorityQueue<i32> {
+        let queue = TPriorityQueue::new();
+        let values: Vec<_> = values.into_iter().collect();
+        atomically(|tx| {
+            for v in &values {
+                queue.push(tx, *v)?;
+            }
+            Ok(())
+        });
+        queue
+    }
+
+    #[test]
+    fn push_pop_order() {
+        let queue = filled([5, 1, 8, 3, 9, 2, 8]);
+        assert_eq!(atomically(|tx| queue.len(tx)), 7);
+        assert_eq!(atomically(|tx| queue.peek(tx)), Some(9));
+
+        let popped: Vec<_> = (0..7).map(|_| atomically(|tx| queue.pop(tx))).collect();
+        assert_eq!(popped, [9, 8, 8, 5, 3, 2, 1]);
+        assert_eq!(atomically(|tx| queue.try_pop(tx)), None);
+        assert!(atomically(|tx| queue.is_empty(tx)));
+    }
+
+    #[test]
+    fn remove_if() {
+        let queue = filled(0..100);
+
+        let mut removed = atomically(|tx| queue.remove_if(tx, |x| x % 3 == 0));
+        removed.sort_unstable();
+        assert_eq!(removed, (0..100).step_by(3).collect::<Vec<_>>());
+
+        let expected: Vec<_> = (0..100).filter(|x| x % 3 != 0).collect();
+        assert_eq!(atomically(|tx| queue.to_sorted_vec(tx)), expected);
+
+        let popped: Vec<_> = (0..expected.len())
+            .map(|_| atomically(|tx| queue.pop(tx)))
+            .collect();
+        assert!(popped.iter().eq(expected.iter().rev()));
+    }
+
+    /// A failed transaction must not leave any trace in the shared nodes.
+    #[test]
+    fn aborted_push() {
+        let queue = filled([1, 3]);
+
+        let mut tx = Transaction::default();
+        queue.push(&mut tx, 2).unwrap();
+        queue.pop(&mut tx).unwrap();
+        drop(tx);
+
+        assert_eq!(atomically(|tx| queue.to_sorted_vec(tx)), [1, 3]);
+    }
+
+    /// `pop` blocks until an element is pushed.
+    #[test]
+    fn threaded_pop() {
+        let queue = TPriorityQueue::new();
+        let queuec = queue.clone();
+
+        let x = crate::test::async_test(
+            800,
+            move || atomically(|tx| queuec.pop(tx)),
+            || {
+                std::thread::sleep(std::time::Duration::from_millis(100));
+                atomically(|tx| queue.push(tx, 42));
+            },
+        );
+
+        assert_eq!(x, Some(42));
+    }
+
+    #[test]
+    fn drop_deep_heap() {
+        // Increasing values build a heap whose left spine holds every node.
+        let queue = filled(0..100_000);
+        drop(queue);
+    }
+}
@@ -0,0 +1,297 @@
+use std::any::Any;
+use std::fmt::{self, Debug};
+
+use crate::{retry, StmClosureResult, TArray, TVar, Transaction};
+
+/// A transactional bounded double-ended queue, implemented as a ring buffer.
+///
+/// The elements are stored in the slots of a `TArray`, between the `front` and `back` indices,
+/// which are stored in their own `TVar`s. Whether the buffer is full or empty is decided by the
+/// occupancy of the slot next to the accessed end, so an operation only reads the index of its
+/// own end. Operations on opposite ends therefore do not conflict, unless the buffer is full
+/// or empty.
+///
+/// Pushing into a full buffer, or popping from an empty one, blocks through `retry`.
+///
+/// Cloning a `TRingBuffer` yields a new handle to the same buffer, like `TVar`.
+///
+/// ```
+/// # use fast_stm::*;
+/// let window = TRingBuffer::new(2);
+///
+/// atomically(|trans| {
+///     window.push_back(trans, 1)?;
+///     window.push_back(trans, 2)
+/// });
+/// assert!(atomically(|trans| window.is_full(trans)));
+///
+/// let oldest = atomically(|trans| {
+///     let oldest = window.pop_front(trans)?;
+///     window.push_back(trans, 3)?;
+///     Ok(oldest)
+/// });
+/// assert_eq!(oldest, 1);
+/// assert_eq!(atomically(|trans| window.to_vec(trans)), [2, 3]);
+/// ```
+pub struct TRingBuffer<T> {
+    /// The slots of the buffer, `None` if unused.
+    slots: TArray<Option<T>>,
+    /// Index of the first element, if any.
+    front: TVar<usize>,
+    /// Index of the slot following the last element.
+    back: TVar<usize>,
+}
+
+impl<T> TRingBuffer<T>
+where
+    T: Any + Send + Sync + Clone,
+{
+    /// Create an empty buffer holding at most `capacity` elements.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is 0.
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "a ring buffer needs a non-zero capacity");
+        Self {
+            slots: TArray::new((0..capacity).map(|_| None)),
+            front: TVar::new(0),
+            back: TVar::new(0),
+        }
+    }
+
+    /// Return the maximum number of elements of the buffer.
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Push an element at the back of the buffer.
+    ///
+    /// Calls `retry` if the buffer is full, blocking the transaction until an element is popped.
+    pub fn push_back(&self, transaction: &mut Transaction, value: T) -> StmClosureResult<()> {
+        let back = self.back.read(transaction)?;
+        if self.slots.read(transaction, back)?.is_some() {
+            return retry();
+        }
+        self.slots.write(transaction, back, Some(value))?;
+        self.back.write(transaction, self.next(back))
+    }
+
+    /// Push an element at the front of the buffer.
+    ///
+    /// Calls `retry` if the buffer is full, blocking the transaction until an element is popped.
+    pub fn push_front(&self, transaction: &mut Transaction, value: T) -> StmClosureResult<()> {
+        let front = self.prev(self.front.read(transaction)?);
+        if self.slots.read(transaction, front)?.is_some() {
+            return retry();
+        }
+        self.slots.write(transaction, front, Some(value))?;
+        self.front.write(transaction, front)
+    }
+
+    /// Remove the first element of the buffer and return it.
+    ///
+    /// Calls `retry` if the buffer is empty, blocking the transaction until an element is pushed.
+    pub fn pop_front(&self, transaction: &mut Transaction) -> StmClosureResult<T> {
+        match self.try_pop_front(transaction)? {
+            Some(value) => Ok(value),
+            None => retry(),
+        }
+    }
+
+    /// Remove the last element of the buffer and return it.
+    ///
+    /// Calls `retry` if the buffer is empty, blocking the transaction until an element is pushed.
+    pub fn pop_back(&self, transaction: &mut Transaction) -> StmClosureResult<T> {
+        match self.try_pop_back(transaction)? {
+            Some(value) => Ok(value),
+            None => retry(),
+        }
+    }
+
+    /// Remove the first element of the buffer and return it, or `None` if the buffer is empty.
+    pub fn try_pop_front(&self, transaction: &mut Transaction) -> StmClosureResult<Option<T>> {
+        let front = self.front.read(transaction)?;
+        if self.slots.read(transaction, front)?.is_none() {
+            return Ok(None);
+        }
+        let value = self.slots.exchange(transaction, front, None)?;
+        self.front.write(transaction, self.next(front))?;
+        Ok(value)
+    }
+
+    /// Remove the last element of the buffer and return it, or `None` if the buffer is empty.
+    pub fn try_pop_back(&self, transaction: &mut Transaction) -> StmClosureResult<Option<T>> {
+        let back = self.prev(self.back.read(transaction)?);
+        if self.slots.read(transaction, back)?.is_none() {
+            return Ok(None);
+        }
+        let value = self.slots.exchange(transaction, back, None)?;
+        self.back.write(transaction, back)?;
+        Ok(value)
+    }
+
+    /// Return the first element of the buffer, if any.
+    pub fn front(&self, transaction: &mut Transaction) -> StmClosureResult<Option<T>> {
+        let front = self.front.read(transaction)?;
+        self.slots.read(transaction, front)
+    }
+
+    /// Return the last element of the buffer, if any.
+    pub fn back(&self, transaction: &mut Transaction) -> StmClosureResult<Option<T>> {
+        let back = self.prev(self.back.read(transaction)?);
+        self.slots.read(transaction, back)
+    }
+
+    /// Return the number of elements of the buffer.
+    ///
+    /// This reads both ends of the buffer.
+    pub fn len(&self, transaction: &mut Transaction) -> StmClosureResult<usize> {
+        let front = self.front.read(transaction)?;
+        let back = self.back.read(transaction)?;
+        if front != back {
+            Ok((back + self.capacity() - front) % self.capacity())
+        } else if self.slots.read(transaction, front)?.is_some() {
+            Ok(self.capacity())
+        } else {
+            Ok(0)
+        }
+    }
+
+    /// Check if the buffer is empty.
+    pub fn is_empty(&self, transaction: &mut Transaction) -> StmClosureResult<bool> {
+        Ok(self.front(transaction)?.is_none())
+    }
+
+    /// Check if the buffer is full.
+    pub fn is_full(&self, transaction: &mut Transaction) -> StmClosureResult<bool> {
+        let back = self.back.read(transaction)?;
+        Ok(self.slots.read(transaction, back)?.is_some())
+    }
+
+    /// Return all elements of the buffer, from front to back.
+    pub fn to_vec(&self, transaction: &mut Transaction) -> StmClosureResult<Vec<T>> {
+        let len = self.len(transaction)?;
+        let front = self.front.read(transaction)?;
+        let mut values = Vec::with_capacity(len);
+        for i in 0..len {
+            values.extend(
+                self.slots
+                    .read(transaction, (front + i) % self.capacity())?,
+            );
+        }
+        Ok(values)
+    }
+
+    fn next(&self, idx: usize) -> usize {
+        (idx + 1) % self.capacity()
+    }
+
+    fn prev(&self, idx: usize) -> usize {
+        (idx + self.capacity() - 1) % self.capacity()
+    }
+}
+
+impl<T> Clone for TRingBuffer<T> {
+    fn clone(&self) -> Self {
+        Self {
+            slots: self.slots.clone(),
+            front: self.front.clone(),
+            back: self.back.clone(),
+        }
+    }
+}
+
+/// Debug output the slots of the buffer.
+impl<T> Debug for TRingBuffer<T>
+where
+    T: Any + Send + Sync + Clone + Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        f.debug_struct("TRingBuffer")
+            .field("slots", &self.slots)
+            .field("front", &self.front.read_atomic())
+            .field("back", &self.back.read_atomic())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::atomically;
+
+    #[test]
+    fn both_ends() {
+        let buffer = TRingBuffer::new(3);
+
+        atomically(|tx| {
+            buffer.push_back(tx, 2)?;
+            buffer.push_front(tx, 1)?;
+            buffer.push_back(tx, 3)
+        });
+        assert_eq!(atomically(|tx| buffer.len(tx)), 3);
+        assert!(atomically(|tx| buffer.is_full(tx)));
+        assert_eq!(atomically(|tx| buffer.to_vec(tx)), [1, 2, 3]);
+
+        assert_eq!(atomically(|tx| buffer.pop_back(tx)), 3);
+        assert_eq!(atomically(|tx| buffer.pop_front(tx)), 1);
+        assert_eq!(atomically(|tx| buffer.front(tx)), Some(2));
+        assert_eq!(atomically(|tx| buffer.back(tx)), Some(2));
+        assert_eq!(atomically(|tx| buffer.pop_back(tx)), 2);
+
+        assert!(atomically(|tx| buffer.is_empty(tx)));
+        assert_eq!(atomically(|tx| buffer.try_pop_front(tx)), None);
+        assert_eq!(atomically(|tx| buffer.try_pop_back(tx)), None);
+    }
+
+    #[test]
+    fn wrap_around() {
+        let buffer = TRingBuffer::new(3);
+        for i in 0..10 {
+            atomically(|tx| buffer.push_back(tx, i));
+            if i >= 2 {
+                assert_eq!(atomically(|tx| buffer.pop_front(tx)), i - 2);
+            }
+        }
+        assert_eq!(atomically(|tx| buffer.to_vec(tx)), [8, 9]);
+    }
+
+    /// Operations on opposite ends should not conflict.
+    #[test]
+    fn opposite_ends_no_conflict() {
+        let buffer = TRingBuffer::new(4);
+        atomically(|tx| buffer.push_back(tx, 0));
+
+        let mut tx1 = Transaction::default();
+        let mut tx2 = Transaction::default();
+        buffer.push_back(&mut tx1, 1).unwrap();
+        assert_eq!(buffer.pop_front(&mut tx2).unwrap(), 0);
+        assert!(tx2.commit());
+        assert!(tx1.commit());
+
+        assert_eq!(atomically(|tx| buffer.to_vec(tx)), [1]);
+    }
+
+    /// Pushing into a full buffer blocks until an element is popped.
+    #[test]
+    fn threaded_full() {
+        let buffer = TRingBuffer::new(1);
+        atomically(|tx| buffer.push_back(tx, 1));
+        let bufferc = buffer.clone();
+
+        let x = crate::test::async_test(
+            800,
+            move || {
+                atomically(|tx| bufferc.push_back(tx, 2));
+                atomically(|tx| bufferc.to_vec(tx))
+            },
+            || {
+                std::thread::sleep(std::time::Duration::from_millis(100));
+                atomically(|tx| buffer.pop_front(tx));
+            },
+        );
+
+        assert_eq!(x, Some(vec![2]));
+    }
+}
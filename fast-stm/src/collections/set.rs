@@ -0,0 +1,237 @@
+use std::any::Any;
+use std::borrow::Borrow;
+use std::fmt::{self, Debug};
+
+use super::list::{Link, ListIter, Node};
+use crate::{StmClosureResult, TVar, Transaction};
+
+/// A transactional ordered set, implemented as a sorted linked list.
+///
+/// Like [`TList`][super::TList], each link is stored in its own `TVar`. A transaction reads the
+/// links up to the position of the values it accesses, and only writes the link preceding the
+/// nodes it inserts or removes. Transactions modifying the set conflict with transactions that
+/// traversed the modified link, i.e. transactions accessing greater values.
+///
+/// Operations take linear time; see [`TSkipList`][super::TSkipList] for a logarithmic
+/// alternative.
+///
+/// Cloning a `TSet` yields a new handle to the same set, like `TVar`.
+///
+/// ```
+/// # use fast_stm::*;
+/// let set = TSet::new();
+///
+/// atomically(|trans| {
+///     set.insert(trans, 3)?;
+///     set.insert(trans, 1)?;
+///     set.insert(trans, 2)
+/// });
+///
+/// assert!(atomically(|trans| set.contains(trans, &2)));
+/// assert_eq!(atomically(|trans| set.to_vec(trans)), [1, 2, 3]);
+/// ```
+pub struct TSet<T>
+where
+    T: Any + Send + Sync,
+{
+    head: TVar<Link<T>>,
+}
+
+impl<T> TSet<T>
+where
+    T: Any + Send + Sync + Clone + Ord,
+{
+    /// Create an empty set.
+    pub fn new() -> Self {
+        Self {
+            head: TVar::new(None),
+        }
+    }
+
+    /// Insert `value` into the set.
+    ///
+    /// Return `true` if the value was not already present.
+    pub fn insert(&self, transaction: &mut Transaction, value: T) -> StmClosureResult<bool> {
+        let (pred, succ) = self.find(transaction, &value)?;
+        if succ.as_ref().is_some_and(|node| node.value == value) {
+            return Ok(false);
+        }
+        pred.write(transaction, Some(Node::new(value, succ)))?;
+        Ok(true)
+    }
+
+    /// Remove `value` from the set.
+    ///
+    /// Return `true` if the value was present.
+    pub fn remove<Q>(&self, transaction: &mut Transaction, value: &Q) -> StmClosureResult<bool>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        match self.find(transaction, value)? {
+            (pred, Some(node)) if node.value.borrow() == value => {
+                let next = node.next.read(transaction)?;
+                pred.write(transaction, next)?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Check if the set contains `value`.
+    pub fn contains<Q>(&self, transaction: &mut Transaction, value: &Q) -> StmClosureResult<bool>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let (_, succ) = self.find(transaction, value)?;
+        Ok(succ.is_some_and(|node| node.value.borrow() == value))
+    }
+
+    /// Iterate over the values of the set, in ascending order.
+    pub fn iter<'a>(&self, transaction: &'a mut Transaction) -> ListIter<'a, T> {
+        ListIter::new(transaction, &self.head)
+    }
+
+    /// Return the number of values of the set.
+    ///
+    /// This reads every link of the list.
+    pub fn len(&self, transaction: &mut Transaction) -> StmClosureResult<usize> {
+        self.iter(transaction)
+            .try_fold(0, |len, value| value.map(|_| len + 1))
+    }
+
+    /// Check if the set is empty.
+    pub fn is_empty(&self, transaction: &mut Transaction) -> StmClosureResult<bool> {
+        Ok(self.head.read(transaction)?.is_none())
+    }
+
+    /// Return all values of the set, in ascending order.
+    pub fn to_vec(&self, transaction: &mut Transaction) -> StmClosureResult<Vec<T>> {
+        self.iter(transaction).collect()
+    }
+
+    /// Search the list for `value`.
+    ///
+    /// Return the link that points to the first node whose value is not smaller than `value`,
+    /// as well as this node.
+    fn find<Q>(
+        &self,
+        transaction: &mut Transaction,
+        value: &Q,
+    ) -> StmClosureResult<(TVar<Link<T>>, Link<T>)>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut pred = self.head.clone();
+        loop {
+            match pred.read(transaction)? {
+                Some(node) if node.value.borrow() < value => pred = node.next.clone(),
+                succ => return Ok((pred, succ)),
+            }
+        }
+    }
+}
+
+impl<T> Default for TSet<T>
+where
+    T: Any + Send + Sync + Clone + Ord,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Clone for TSet<T>
+where
+    T: Any + Send + Sync,
+{
+    fn clone(&self) -> Self {
+        Self {
+            head: self.head.clone(),
+        }
+    }
+}
+
+/// Debug output the set.
+impl<T> Debug for TSet<T>
+where
+    T: Any + Send + Sync + Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        let mut set = f.debug_set();
+        let mut link = self.head.read_atomic();
+        while let Some(node) = link {
+            set.entry(&node.value);
+            link = node.next.read_atomic();
+        }
+        set.finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::atomically;
+
+    #[test]
+    fn insert_remove() {
+        let set = TSet::new();
+
+        assert!(atomically(|tx| set.insert(tx, 2)));
+        assert!(atomically(|tx| set.insert(tx, 1)));
+        assert!(!atomically(|tx| set.insert(tx, 2)));
+        assert_eq!(atomically(|tx| set.len(tx)), 2);
+
+        assert!(atomically(|tx| set.remove(tx, &1)));
+        assert!(!atomically(|tx| set.remove(tx, &1)));
+        assert!(!atomically(|tx| set.contains(tx, &1)));
+        assert!(atomically(|tx| set.contains(tx, &2)));
+        assert_eq!(atomically(|tx| set.to_vec(tx)), [2]);
+    }
+
+    /// Modifying the end of the set does not conflict with lookups of smaller values.
+    #[test]
+    fn prefix_no_conflict() {
+        let set = TSet::new();
+        atomically(|tx| {
+            for v in [1, 2, 3] {
+                set.insert(tx, v)?;
+            }
+            Ok(())
+        });
+
+        let mut tx1 = Transaction::default();
+        let mut tx2 = Transaction::default();
+        assert!(set.contains(&mut tx1, &1).unwrap());
+        assert!(set.remove(&mut tx2, &3).unwrap());
+        assert!(tx2.commit());
+        assert!(tx1.commit());
+    }
+
+    #[test]
+    fn threaded_inserts() {
+        let set = TSet::new();
+
+        let handles: Vec<_> = (0..4)
+            .map(|t| {
+                let set = set.clone();
+                std::thread::spawn(move || {
+                    for i in 0..100 {
+                        atomically(|tx| set.insert(tx, i * 4 + t));
+                        if i % 2 == 1 {
+                            atomically(|tx| set.remove(tx, &(i * 4 + t)));
+                        }
+                    }
+                })
+            })
+            .collect();
+        for h in handles {
+            h.join().unwrap();
+        }
+
+        let expected: Vec<_> = (0..400).filter(|v| (v / 4) % 2 == 0).collect();
+        assert_eq!(atomically(|tx| set.to_vec(tx)), expected);
+    }
+}
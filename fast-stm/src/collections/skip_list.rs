@@ -0,0 +1,518 @@
+use std::any::Any;
+use std::borrow::Borrow;
+use std::cell::Cell;
+use std::collections::hash_map::RandomState;
+use std::fmt::{self, Debug};
+use std::hash::BuildHasher;
+use std::ops::{Bound, RangeBounds};
+use std::sync::Arc;
+
+use super::take_link;
+use crate::{StmClosureResult, TVar, Transaction};
+
+/// Maximum height of a node.
+///
+/// With a promotion probability of 1/2, this is enough for lists of a few ten thousands of
+/// elements before performances start degrading.
+const MAX_LEVEL: usize = 16;
+
+/// Link to the next node of a given level.
+type Link<K, V> = Option<Arc<Node<K, V>>>;
+
+struct Node<K, V>
+where
+    K: Any + Send + Sync,
+    V: Any + Send + Sync,
+{
+    key: K,
+    /// The value has its own `TVar`, so that updating it does not touch the links.
+    value: TVar<V>,
+    /// Links to the next node of each level this node belongs to.
+    next: Box<[TVar<Link<K, V>>]>,
+}
+
+impl<K, V> Drop for Node<K, V>
+where
+    K: Any + Send + Sync,
+    V: Any + Send + Sync,
+{
+    fn drop(&mut self) {
+        // Dropping a node drops its successor if this was the last reference to it, which
+        // recurses through the whole list. Unlink the chain iteratively instead.
+        let mut link = take_link(&self.next[0]);
+        while let Some(node) = link {
+            match Arc::try_unwrap(node) {
+                Ok(node) => link = take_link(&node.next[0]),
+                Err(_) => break,
+            }
+        }
+    }
+}
+
+/// Draw the height of a new node.
+fn random_level() -> usize {
+    thread_local!(static STATE: Cell<u64> = Cell::new(RandomState::new().hash_one(0_u8) | 1));
+
+    STATE.with(|state| {
+        // xorshift64
+        let mut x = state.get();
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        state.set(x);
+        (x.trailing_ones() as usize + 1).min(MAX_LEVEL)
+    })
+}
+
+/// A transactional ordered map, implemented as a skip list.
+///
+/// Each link of the list is stored in its own `TVar`, as is each value. A transaction only
+/// reads the links on the search path of the keys it accesses, and only writes the links
+/// surrounding the nodes it inserts or removes. Transactions touching distant keys therefore
+/// do not conflict.
+///
+/// Operations spanning the whole list (`len`, `to_vec`, ...) read all links of the bottom level,
+/// and therefore conflict with any concurrent insertion or removal.
+///
+/// Cloning a `TSkipList` yields a new handle to the same list, like `TVar`.
+///
+/// ```
+/// # use fast_stm::*;
+/// let book = TSkipList::new();
+///
+/// atomically(|trans| {
+///     book.insert(trans, 3, "c")?;
+///     book.insert(trans, 1, "a")?;
+///     book.insert(trans, 2, "b")?;
+///     Ok(())
+/// });
+///
+/// let (first, range) = atomically(|trans| Ok((book.first(trans)?, book.range(trans, 2..)?)));
+/// assert_eq!(first, Some((1, "a")));
+/// assert_eq!(range, vec![(2, "b"), (3, "c")]);
+/// ```
+pub struct TSkipList<K, V>
+where
+    K: Any + Send + Sync,
+    V: Any + Send + Sync,
+{
+    head: Arc<[TVar<Link<K, V>>]>,
+}
+
+impl<K, V> TSkipList<K, V>
+where
+    K: Any + Send + Sync + Clone + Ord,
+    V: Any + Send + Sync + Clone,
+{
+    /// Create an empty list.
+    pub fn new() -> Self {
+        Self {
+            head: (0..MAX_LEVEL).map(|_| TVar::new(None)).collect(),
+        }
+    }
+
+    /// Return the value associated to `key`, if any.
+    pub fn get<Q>(&self, transaction: &mut Transaction, key: &Q) -> StmClosureResult<Option<V>>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        match self.find(transaction, key)?.1 {
+            Some(node) if node.key.borrow() == key => node.value.read(transaction).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// Check if the list contains a value for `key`.
+    pub fn contains_key<Q>(&self, transaction: &mut Transaction, key: &Q) -> StmClosureResult<bool>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        Ok(matches!(self.find(transaction, key)?.1, Some(node) if node.key.borrow() == key))
+    }
+
+    /// Insert a key-value pair into the list, returning the previous value of `key`, if any.
+    ///
+    /// If `key` is already present, only the `TVar` holding its value is written.
+    pub fn insert(
+        &self,
+        transaction: &mut Transaction,
+        key: K,
+        value: V,
+    ) -> StmClosureResult<Option<V>> {
+        let (preds, succ) = self.find(transaction, &key)?;
+        if let Some(node) = succ.filter(|node| node.key == key) {
+            return node.value.exchange(transaction, value).map(Some);
+        }
+
+        let height = random_level();
+        let mut next = Vec::with_capacity(height);
+        for pred in &preds[..height] {
+            next.push(TVar::new(pred.read(transaction)?));
+        }
+        let node = Arc::new(Node {
+            key,
+            value: TVar::new(value),
+            next: next.into_boxed_slice(),
+        });
+        for pred in &preds[..height] {
+            pred.write(transaction, Some(node.clone()))?;
+        }
+        Ok(None)
+    }
+
+    /// Remove `key` from the list, returning its value, if any.
+    pub fn remove<Q>(&self, transaction: &mut Transaction, key: &Q) -> StmClosureResult<Option<V>>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let (preds, succ) = self.find(transaction, key)?;
+        match succ {
+            Some(node) if node.key.borrow() == key => {
+                Self::unlink(transaction, &preds, &node)?;
+                node.value.read(transaction).map(Some)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Return the entry with the smallest key, if any.
+    pub fn first(&self, transaction: &mut Transaction) -> StmClosureResult<Option<(K, V)>> {
+        match self.head[0].read(transaction)? {
+            Some(node) => Self::entry(transaction, &node).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Return the entry with the largest key, if any.
+    pub fn last(&self, transaction: &mut Transaction) -> StmClosureResult<Option<(K, V)>> {
+        let mut pred: Link<K, V> = None;
+        for level in (0..MAX_LEVEL).rev() {
+            while let Some(next) = self.link(&pred, level).read(transaction)? {
+                pred = Some(next);
+            }
+        }
+        match pred {
+            Some(node) => Self::entry(transaction, &node).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Remove the entry with the smallest key and return it, if any.
+    ///
+    /// This only writes the head links pointing to the first node.
+    pub fn pop_first(&self, transaction: &mut Transaction) -> StmClosureResult<Option<(K, V)>> {
+        match self.head[0].read(transaction)? {
+            Some(node) => {
+                Self::unlink(transaction, &self.head, &node)?;
+                Self::entry(transaction, &node).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Return the entries whose key is contained in `range`, in ascending order.
+    pub fn range<Q, R>(
+        &self,
+        transaction: &mut Transaction,
+        range: R,
+    ) -> StmClosureResult<Vec<(K, V)>>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        let mut link = match range.start_bound() {
+            Bound::Included(start) => self.find(transaction, start)?.1,
+            Bound::Excluded(start) => match self.find(transaction, start)?.1 {
+                Some(node) if node.key.borrow() == start => node.next[0].read(transaction)?,
+                succ => succ,
+            },
+            Bound::Unbounded => self.head[0].read(transaction)?,
+        };
+
+        let mut entries = Vec::new();
+        while let Some(node) = link {
+            let in_range = match range.end_bound() {
+                Bound::Included(end) => node.key.borrow() <= end,
+                Bound::Excluded(end) => node.key.borrow() < end,
+                Bound::Unbounded => true,
+            };
+            if !in_range {
+                break;
+            }
+            entries.push(Self::entry(transaction, &node)?);
+            link = node.next[0].read(transaction)?;
+        }
+        Ok(entries)
+    }
+
+    /// Split the list in two at `key`.
+    ///
+    /// Return a new list containing all entries whose key is greater than or equal to `key`,
+    /// which are removed from `self`. Nodes are moved to the new list, not copied.
+    pub fn split_off<Q>(&self, transaction: &mut Transaction, key: &Q) -> StmClosureResult<Self>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let (preds, _) = self.find(transaction, key)?;
+        let mut head = Vec::with_capacity(MAX_LEVEL);
+        for pred in &preds {
+            head.push(TVar::new(pred.read(transaction)?));
+            pred.write(transaction, None)?;
+        }
+        Ok(Self { head: head.into() })
+    }
+
+    /// Return the number of entries of the list.
+    ///
+    /// This reads every link of the bottom level.
+    pub fn len(&self, transaction: &mut Transaction) -> StmClosureResult<usize> {
+        let mut len = 0;
+        let mut link = self.head[0].read(transaction)?;
+        while let Some(node) = link {
+            len += 1;
+            link = node.next[0].read(transaction)?;
+        }
+        Ok(len)
+    }
+
+    /// Check if the list is empty.
+    pub fn is_empty(&self, transaction: &mut Transaction) -> StmClosureResult<bool> {
+        Ok(self.head[0].read(transaction)?.is_none())
+    }
+
+    /// Return all entries of the list, in ascending order.
+    pub fn to_vec(&self, transaction: &mut Transaction) -> StmClosureResult<Vec<(K, V)>> {
+        self.range::<K, _>(transaction, ..)
+    }
+
+    /// Search the list for `key`.
+    ///
+    /// Return, for each level, the link that points to the first node whose key is not smaller
+    /// than `key`, as well as this node of the bottom level.
+    #[allow(clippy::type_complexity)]
+    fn find<Q>(
+        &self,
+        transaction: &mut Transaction,
+        key: &Q,
+    ) -> StmClosureResult<(Box<[TVar<Link<K, V>>]>, Link<K, V>)>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut preds: Box<[TVar<Link<K, V>>]> = self.head.iter().cloned().collect();
+        let mut pred: Link<K, V> = None;
+        let mut succ = None;
+        for level in (0..MAX_LEVEL).rev() {
+            loop {
+                let next = self.link(&pred, level).read(transaction)?;
+                match next {
+                    Some(node) if node.key.borrow() < key => pred = Some(node),
+                    next => {
+                        succ = next;
+                        break;
+                    }
+                }
+            }
+            preds[level] = self.link(&pred, level).clone();
+        }
+        Ok((preds, succ))
+    }
+
+    /// Return the link of a given level going out of `pred`, or out of the head if `None`.
+    fn link<'a>(&'a self, pred: &'a Link<K, V>, level: usize) -> &'a TVar<Link<K, V>> {
+        match pred {
+            Some(node) => &node.next[level],
+            None => &self.head[level],
+        }
+    }
+
+    /// Unlink `node`, given the links that point to it.
+    fn unlink(
+        transaction: &mut Transaction,
+        preds: &[TVar<Link<K, V>>],
+        node: &Node<K, V>,
+    ) -> StmClosureResult<()> {
+        for (pred, next) in preds.iter().zip(node.next.iter()) {
+            let next = next.read(transaction)?;
+            pred.write(transaction, next)?;
+        }
+        Ok(())
+    }
+
+    fn entry(transaction: &mut Transaction, node: &Node<K, V>) -> StmClosureResult<(K, V)> {
+        Ok((node.key.clone(), node.value.read(transaction)?))
+    }
+}
+
+impl<K, V> Default for TSkipList<K, V>
+where
+    K: Any + Send + Sync + Clone + Ord,
+    V: Any + Send + Sync + Clone,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V> Clone for TSkipList<K, V>
+where
+    K: Any + Send + Sync,
+    V: Any + Send + Sync,
+{
+    fn clone(&self) -> Self {
+        Self {
+            head: self.head.clone(),
+        }
+    }
+}
+
+/// Debug output the list.
+impl<K, V> Debug for TSkipList<K, V>
+where
+    K: Any + Send + Sync + Clone + Debug,
+    V: Any + Send + Sync + Clone + Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        let mut map = f.debug_map();
+        let mut link = self.head[0].read_atomic();
+        while let Some(node) = link {
+            map.entry(&node.key, &node.value.read_atomic());
+            link = node.next[0].read_atomic();
+        }
+        map.finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::atomically;
+
+    fn filled(keys: impl IntoIterator<Item = i32>) -> TSkipList<i32, i32> {
+        let list = TSkipList::new();
+        let keys: Vec<_> = keys.into_iter().collect();
+        atomically(|tx| {
+            for k in &keys {
+                list.insert(tx, *k, k * 10)?;
+            }
+            Ok(())
+        });
+        list
+    }
+
+    #[test]
+    fn insert_get_remove() {
+        let list = filled([5, 1, 3]);
+
+        assert_eq!(atomically(|tx| list.insert(tx, 3, 0)), Some(30));
+        assert_eq!(atomically(|tx| list.get(tx, &3)), Some(0));
+        assert_eq!(atomically(|tx| list.remove(tx, &3)), Some(0));
+        assert_eq!(atomically(|tx| list.remove(tx, &3)), None);
+        assert!(!atomically(|tx| list.contains_key(tx, &3)));
+        assert_eq!(atomically(|tx| list.to_vec(tx)), vec![(1, 10), (5, 50)]);
+    }
+
+    #[test]
+    fn ordered_accessors() {
+        let list = filled([4, 2, 8, 6]);
+
+        assert_eq!(atomically(|tx| list.first(tx)), Some((2, 20)));
+        assert_eq!(atomically(|tx| list.last(tx)), Some((8, 80)));
+        assert_eq!(atomically(|tx| list.pop_first(tx)), Some((2, 20)));
+        assert_eq!(atomically(|tx| list.first(tx)), Some((4, 40)));
+        assert_eq!(atomically(|tx| list.len(tx)), 3);
+
+        let empty: TSkipList<i32, i32> = TSkipList::new();
+        assert_eq!(atomically(|tx| empty.last(tx)), None);
+        assert_eq!(atomically(|tx| empty.pop_first(tx)), None);
+    }
+
+    #[test]
+    fn range_queries() {
+        let list = filled(0..20);
+
+        let keys = |v: Vec<(i32, i32)>| v.into_iter().map(|(k, _)| k).collect::<Vec<_>>();
+        assert_eq!(keys(atomically(|tx| list.range(tx, 5..8))), [5, 6, 7]);
+        assert_eq!(keys(atomically(|tx| list.range(tx, 17..))), [17, 18, 19]);
+        assert_eq!(keys(atomically(|tx| list.range(tx, ..=2))), [0, 1, 2]);
+        assert_eq!(
+            keys(atomically(|tx| {
+                list.range(tx, (Bound::Excluded(10), Bound::Included(12)))
+            })),
+            [11, 12]
+        );
+        assert!(atomically(|tx| list.range(tx, 30..)).is_empty());
+    }
+
+    #[test]
+    fn split_off() {
+        let list = filled(0..10);
+
+        let tail = atomically(|tx| list.split_off(tx, &6));
+        let keys = |l: &TSkipList<i32, i32>| {
+            atomically(|tx| l.to_vec(tx))
+                .into_iter()
+                .map(|(k, _)| k)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(keys(&list), [0, 1, 2, 3, 4, 5]);
+        assert_eq!(keys(&tail), [6, 7, 8, 9]);
+
+        // both lists remain usable
+        atomically(|tx| list.insert(tx, 7, 0));
+        atomically(|tx| tail.remove(tx, &8));
+        assert_eq!(keys(&list), [0, 1, 2, 3, 4, 5, 7]);
+        assert_eq!(keys(&tail), [6, 7, 9]);
+        assert_eq!(atomically(|tx| tail.last(tx)), Some((9, 90)));
+    }
+
+    /// Updating values of distinct keys should not conflict.
+    #[test]
+    fn disjoint_values_do_not_conflict() {
+        let list = filled(0..100);
+
+        let mut tx1 = Transaction::default();
+        let mut tx2 = Transaction::default();
+        list.insert(&mut tx1, 10, 0).unwrap();
+        list.insert(&mut tx2, 90, 0).unwrap();
+
+        assert!(tx2.commit());
+        assert!(tx1.commit());
+    }
+
+    #[test]
+    fn threaded_inserts() {
+        let list = TSkipList::new();
+
+        let handles: Vec<_> = (0..4)
+            .map(|t| {
+                let list = list.clone();
+                std::thread::spawn(move || {
+                    for i in 0..100 {
+                        atomically(|tx| list.insert(tx, i * 4 + t, ()));
+                    }
+                })
+            })
+            .collect();
+        for h in handles {
+            h.join().unwrap();
+        }
+
+        let keys: Vec<_> = atomically(|tx| list.to_vec(tx))
+            .into_iter()
+            .map(|(k, ())| k)
+            .collect();
+        assert_eq!(keys, (0..400).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn drop_long_list() {
+        let list = filled(0..20_000);
+        drop(list);
+    }
+}
@@ -0,0 +1,468 @@
+//! Recording of transaction attempts, for offline analysis.
+//!
+//! Each attempt is written as a single JSON object on its own line. Commits are numbered while
+//! the committing transaction holds the locks of all the variables it accessed, so the numbers
+//! give a serialization order of the committed transactions. Each attempt also records the range
+//! of commit numbers issued while it ran, which bounds the commits that may have caused it to
+//! fail. The `stm-log` tool of the repository reads these files.
+
+use std::any::{Any, TypeId};
+use std::fmt::{self, Debug, Write as _};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::Instant;
+
+use parking_lot::{Mutex, RwLock};
+
+use crate::result::FailureReason;
+use crate::transaction::log_var::{ArcAny, LogVar};
+use crate::tvar::{VarId, VarRef};
+
+/// Set while a log is being recorded, checked before doing any work.
+static RECORDING: AtomicBool = AtomicBool::new(false);
+
+/// Number of the next commit.
+static COMMIT_SEQ: AtomicU64 = AtomicU64::new(0);
+
+static LOG: Mutex<Log> = Mutex::new(Log {
+    sink: None,
+    error: None,
+});
+
+/// Types whose values are written to the log.
+///
+/// They are kept apart from `LOG`, so that values are rendered without holding it: a `Debug`
+/// implementation may run transactions, which record themselves.
+static RENDERERS: RwLock<Vec<(TypeId, Renderer)>> = RwLock::new(Vec::new());
+
+/// Function formatting a value with the `Debug` implementation of its type.
+type Renderer = fn(&ArcAny) -> String;
+
+struct Log {
+    sink: Option<Sink>,
+    /// First error returned by the output, reported by `EventLog::stop`.
+    error: Option<io::Error>,
+}
+
+struct Sink {
+    out: Box<dyn Write + Send>,
+    /// Origin of the timestamps.
+    epoch: Instant,
+}
+
+/// Records every attempt of every transaction of the process, for offline analysis.
+///
+/// Each line of the log describes one attempt: the thread and transaction running it, the
+/// variables it read and wrote, its outcome and its timing. Only the identifiers of variables
+/// are recorded, unless their type is registered with `render`.
+///
+/// Recording serializes the attempts of all threads on a single lock, which changes the timing
+/// of the program; it is meant for reproducing bugs, not for production.
+///
+/// ```
+/// # use fast_stm::*;
+/// # fn run() -> std::io::Result<()> {
+/// # let path = std::env::temp_dir().join("fast-stm-event-log-doc.jsonl");
+/// EventLog::render::<i32>();
+/// EventLog::create(&path)?;
+///
+/// let balance = TVar::with_name(0, "balance");
+/// atomically_named("deposit", |trans| balance.modify(trans, |x| x + 10));
+///
+/// EventLog::stop()?;
+/// # std::fs::remove_file(path)
+/// # }
+/// # run().unwrap();
+/// ```
+pub struct EventLog;
+
+impl EventLog {
+    /// Start recording attempts to `out`, replacing the current output, if any.
+    ///
+    /// Commits are numbered from zero in each log.
+    pub fn record<W>(out: W)
+    where
+        W: Write + Send + 'static,
+    {
+        let mut log = LOG.lock();
+        log.sink = Some(Sink {
+            out: Box::new(out),
+            epoch: Instant::now(),
+        });
+        log.error = None;
+        COMMIT_SEQ.store(0, Ordering::Relaxed);
+        RECORDING.store(true, Ordering::Relaxed);
+    }
+
+    /// Start recording attempts to the file at `path`, created or truncated.
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<()> {
+        let file = File::create(path)?;
+        Self::record(BufWriter::new(file));
+        Ok(())
+    }
+
+    /// Stop recording and flush the output.
+    ///
+    /// Return the first error met while writing the log, if any.
+    pub fn stop() -> io::Result<()> {
+        let mut log = LOG.lock();
+        RECORDING.store(false, Ordering::Relaxed);
+        let flushed = match log.sink.take() {
+            Some(mut sink) => sink.out.flush(),
+            None => Ok(()),
+        };
+        match log.error.take() {
+            Some(err) => Err(err),
+            None => flushed,
+        }
+    }
+
+    /// Write the values of variables of type `T` to the log, using their `Debug` format.
+    ///
+    /// Registered types stay registered across logs.
+    pub fn render<T>()
+    where
+        T: Any + Debug,
+    {
+        let mut renderers = RENDERERS.write();
+        let type_id = TypeId::of::<T>();
+        if !renderers.iter().any(|(id, _)| *id == type_id) {
+            renderers.push((type_id, render_value::<T>));
+        }
+    }
+}
+
+/// Format a value with the renderer of its type, if it is registered.
+fn render(value: &ArcAny) -> Option<String> {
+    let type_id = (**value).type_id();
+    let renderer = RENDERERS
+        .read()
+        .iter()
+        .find(|(id, _)| *id == type_id)
+        .map(|(_, render)| *render);
+    renderer.map(|render| render(value))
+}
+
+/// Format a value of type `T`.
+fn render_value<T: Any + Debug>(value: &ArcAny) -> String {
+    value
+        .downcast_ref::<T>()
+        .map_or_else(String::new, |value| format!("{value:?}"))
+}
+
+/// Check if attempts are being recorded.
+pub(crate) fn recording() -> bool {
+    RECORDING.load(Ordering::Relaxed)
+}
+
+/// Number a commit.
+///
+/// This must be called while holding the locks of all the variables accessed by the
+/// transaction, so that the numbers are consistent with the order of the commits.
+pub(crate) fn next_commit() -> u64 {
+    COMMIT_SEQ.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Return the number of the thread, in order of first recorded attempt.
+fn thread() -> u64 {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    thread_local!(static THREAD: u64 = NEXT.fetch_add(1, Ordering::Relaxed));
+    THREAD.with(|t| *t)
+}
+
+/// How an attempt ended.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Outcome {
+    /// The attempt committed, with the given number.
+    Commit(u64),
+    /// Validation of the variable failed at commit.
+    Conflict(VarId),
+    /// The transaction body returned an error.
+    Failed(FailureReason),
+    /// The transaction body called `abort`.
+    Cancel,
+}
+
+/// Attempt of a running transaction, as tracked for the log.
+pub(crate) struct Attempt {
+    /// Number of the attempt in the transaction, starting from 1.
+    n: u64,
+    start: Instant,
+    /// Number of the next commit when the attempt started.
+    from_seq: u64,
+}
+
+impl Default for Attempt {
+    fn default() -> Self {
+        Self {
+            n: 1,
+            start: Instant::now(),
+            from_seq: COMMIT_SEQ.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl Attempt {
+    /// Start the next attempt.
+    pub(crate) fn next(&mut self) {
+        *self = Self {
+            n: self.n + 1,
+            ..Self::default()
+        };
+    }
+
+    /// Write the attempt to the log.
+    ///
+    /// `vars` is the log of the attempt. The values written by a commit are passed in `written`,
+    /// so that the log holds the results of commutative updates.
+    ///
+    /// Rendering values may read other variables, so this must not be called while holding the
+    /// locks of variables.
+    pub(crate) fn record<'a, I>(
+        &self,
+        name: Option<&'static str>,
+        vars: I,
+        written: &[(&VarRef, ArcAny)],
+        outcome: Outcome,
+    ) where
+        I: IntoIterator<Item = (&'a VarRef, &'a LogVar)>,
+    {
+        let end = Instant::now();
+        let to_seq = COMMIT_SEQ.load(Ordering::Relaxed);
+
+        // Render the values before taking the lock of the log.
+        let mut reads = Vec::new();
+        let mut writes = Vec::new();
+        for (var, value) in vars {
+            match value {
+                LogVar::Read(v) => reads.push((var.id(), render(v))),
+                LogVar::ReadWrite(v, w) => {
+                    reads.push((var.id(), render(v)));
+                    if written.is_empty() {
+                        writes.push((var.id(), render(w)));
+                    }
+                }
+                LogVar::Write(w) | LogVar::ReadObsoleteWrite(_, w) if written.is_empty() => {
+                    writes.push((var.id(), render(w)));
+                }
+                LogVar::Commute(_) if written.is_empty() => writes.push((var.id(), None)),
+                _ => {}
+            }
+        }
+        for (var, value) in written {
+            writes.push((var.id(), render(value)));
+        }
+
+        let mut guard = LOG.lock();
+        let log = &mut *guard;
+        let Some(sink) = log.sink.as_mut() else {
+            return;
+        };
+
+        let record = Record {
+            thread: thread(),
+            name,
+            attempt: self.n,
+            start: self.start.saturating_duration_since(sink.epoch).as_nanos(),
+            end: end.saturating_duration_since(sink.epoch).as_nanos(),
+            from_seq: self.from_seq,
+            to_seq,
+            outcome,
+            reads: &reads,
+            writes: &writes,
+        };
+        // Format the line first, so that a failed write does not leave a partial line.
+        let line = format!("{record}\n");
+        if let Err(err) = sink.out.write_all(line.as_bytes()) {
+            log.error.get_or_insert(err);
+            log.sink = None;
+            RECORDING.store(false, Ordering::Relaxed);
+        }
+    }
+}
+
+/// A line of the log.
+struct Record<'a> {
+    thread: u64,
+    name: Option<&'static str>,
+    attempt: u64,
+    start: u128,
+    end: u128,
+    from_seq: u64,
+    to_seq: u64,
+    outcome: Outcome,
+    reads: &'a [(VarId, Option<String>)],
+    writes: &'a [(VarId, Option<String>)],
+}
+
+impl fmt::Display for Record<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, r#"{{"thread":{},"transaction":"#, self.thread)?;
+        write_option(f, self.name)?;
+        write!(
+            f,
+            r#","attempt":{},"start_ns":{},"end_ns":{},"from_seq":{},"to_seq":{},"outcome":"#,
+            self.attempt, self.start, self.end, self.from_seq, self.to_seq
+        )?;
+        match self.outcome {
+            Outcome::Commit(seq) => write!(f, r#""commit","seq":{seq}"#)?,
+            Outcome::Conflict(var) => {
+                f.write_str(r#""conflict","var":"#)?;
+                write_var(f, var)?;
+            }
+            Outcome::Failed(FailureReason::Retry) => f.write_str(r#""retry""#)?,
+            Outcome::Failed(FailureReason::EarlyConflict) => f.write_str(r#""early_conflict""#)?,
+            Outcome::Failed(_) => f.write_str(r#""failure""#)?,
+            Outcome::Cancel => f.write_str(r#""cancel""#)?,
+        }
+        f.write_str(r#","reads":"#)?;
+        write_accesses(f, self.reads)?;
+        f.write_str(r#","writes":"#)?;
+        write_accesses(f, self.writes)?;
+        f.write_str("}")
+    }
+}
+
+/// Write a list of accessed variables, with their values.
+fn write_accesses(f: &mut fmt::Formatter, accesses: &[(VarId, Option<String>)]) -> fmt::Result {
+    f.write_str("[")?;
+    for (i, (var, value)) in accesses.iter().enumerate() {
+        if i > 0 {
+            f.write_str(",")?;
+        }
+        f.write_str(r#"{"var":"#)?;
+        write_var(f, *var)?;
+        if let Some(value) = value {
+            f.write_str(r#","value":"#)?;
+            write_string(f, value)?;
+        }
+        f.write_str("}")?;
+    }
+    f.write_str("]")
+}
+
+/// Write a variable as an object holding its address and name.
+fn write_var(f: &mut fmt::Formatter, var: VarId) -> fmt::Result {
+    write!(f, r#"{{"address":{},"name":"#, var.address())?;
+    write_option(f, var.name())?;
+    f.write_str("}")
+}
+
+fn write_option(f: &mut fmt::Formatter, s: Option<&str>) -> fmt::Result {
+    match s {
+        Some(s) => write_string(f, s),
+        None => f.write_str("null"),
+    }
+}
+
+/// Write a JSON string literal.
+fn write_string(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str(r#"\""#)?,
+            '\\' => f.write_str(r"\\")?,
+            '\n' => f.write_str(r"\n")?,
+            '\r' => f.write_str(r"\r")?,
+            '\t' => f.write_str(r"\t")?,
+            c if c.is_control() => write!(f, r"\u{:04x}", u32::from(c))?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{atomically, atomically_named, TVar, Transaction};
+
+    /// Output shared with the test.
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// A value whose `Debug` implementation waits for a transaction of another thread.
+    #[derive(Clone)]
+    struct Nested(TVar<i32>);
+
+    impl Debug for Nested {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            let value = std::thread::scope(|s| {
+                s.spawn(|| atomically(|trans| self.0.read(trans)))
+                    .join()
+                    .unwrap()
+            });
+            write!(f, "Nested({value})")
+        }
+    }
+
+    /// The log is global, so all scenarios run in a single test, and only lines of the
+    /// transactions of this test are kept.
+    #[test]
+    fn records_attempts() {
+        EventLog::render::<i32>();
+        EventLog::render::<String>();
+        EventLog::render::<Nested>();
+        let buffer = Buffer::default();
+        EventLog::record(buffer.clone());
+
+        let balance = TVar::with_name(10, "balance");
+        let owner = TVar::with_name(String::from("a \"quoted\" name"), "owner");
+        let hits = TVar::new(0);
+        atomically_named("event-log-deposit", |trans| {
+            owner.read_arc(trans)?;
+            hits.commute(trans, |x| x + 1)?;
+            balance.modify(trans, |x| x + 5)
+        });
+        let first = AtomicBool::new(true);
+        Transaction::with_name("event-log-failure", |trans| {
+            balance.read(trans)?;
+            if first.swap(false, Ordering::Relaxed) {
+                return Err(crate::StmError::Failure);
+            }
+            Ok(())
+        });
+        // rendering a value may wait for another transaction
+        let inner = TVar::new(7);
+        let nested = TVar::new(Nested(inner));
+        atomically_named("event-log-nested", |trans| nested.read(trans).map(drop));
+        assert!(Transaction::with_err(|_| crate::abort::<(), _>(())).is_err());
+
+        EventLog::stop().unwrap();
+        let text = String::from_utf8(buffer.0.lock().clone()).unwrap();
+        let lines: Vec<_> = text
+            .lines()
+            .filter(|line| line.contains(r#""transaction":"event-log-"#))
+            .collect();
+
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].contains(r#""attempt":1,"#));
+        assert!(lines[0].contains(r#""outcome":"commit","seq":"#));
+        assert!(lines[0].contains(r#""name":"balance"},"value":"10""#));
+        assert!(lines[0].contains(r#""name":"balance"},"value":"15""#));
+        assert!(lines[0].contains(r#""name":"owner"},"value":"\"a \\\"quoted\\\" name\"""#));
+        // the commutation is recorded with the committed value
+        assert!(lines[0].contains(r#""name":null},"value":"1""#));
+        assert!(lines[1].contains(r#""outcome":"failure""#));
+        assert!(lines[2].contains(r#""attempt":2,"#));
+        assert!(lines[2].contains(r#""outcome":"commit""#));
+        assert!(lines[3].contains(r#""value":"Nested(7)""#));
+        assert!(text.contains(r#""transaction":null,"#));
+        assert!(text.contains(r#""outcome":"cancel""#));
+    }
+}
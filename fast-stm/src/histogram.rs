@@ -0,0 +1,415 @@
+//! Histograms of transaction behaviour, recorded by the `profiling` feature.
+//!
+//! Each thread records into its own histograms, grouped by transaction name, so that recording
+//! never contends with other threads. Snapshots merge the histograms of all threads.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{self, Debug};
+use std::iter::Sum;
+use std::ops::AddAssign;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+
+/// Number of bits of a value kept by its bucket: each power of two is split into `2^SUB_BITS`
+/// buckets, so that the relative error of a bucket is at most `2^-SUB_BITS`.
+const SUB_BITS: u32 = 4;
+const SUB_BUCKETS: u64 = 1 << SUB_BITS;
+
+/// Return the index of the bucket holding `value`.
+///
+/// Indices are smaller than 1024, so the casts are lossless.
+#[allow(clippy::cast_possible_truncation)]
+fn bucket(value: u64) -> usize {
+    if value < SUB_BUCKETS {
+        return value as usize;
+    }
+    let exp = value.ilog2();
+    let sub = (value >> (exp - SUB_BITS)) & (SUB_BUCKETS - 1);
+    ((exp - SUB_BITS + 1) as usize) * SUB_BUCKETS as usize + sub as usize
+}
+
+/// Return the smallest and the greatest value of bucket `idx`.
+fn bounds(idx: usize) -> (u64, u64) {
+    let idx = idx as u64;
+    if idx < SUB_BUCKETS {
+        return (idx, idx);
+    }
+    let shift = idx / SUB_BUCKETS - 1;
+    let low = (SUB_BUCKETS + idx % SUB_BUCKETS) << shift;
+    (low, low + ((1 << shift) - 1))
+}
+
+/// A histogram of `u64` values, with log-linear buckets.
+///
+/// Values below 16 are counted exactly; greater values are counted in buckets whose width is at
+/// most 1/16 of their lower bound. Buckets are allocated as needed.
+///
+/// Histograms can be merged with `+=` or `sum`.
+///
+/// ```
+/// # use fast_stm::*;
+/// let mut h = Histogram::default();
+/// for v in 1..=100 {
+///     h.record(v);
+/// }
+///
+/// assert_eq!(h.count(), 100);
+/// assert_eq!(h.max(), Some(100));
+/// assert!(h.quantile(0.5).unwrap().abs_diff(50) <= 50 / 16);
+/// ```
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Histogram {
+    counts: Vec<u64>,
+    count: u64,
+    sum: u64,
+    min: u64,
+    max: u64,
+}
+
+impl Histogram {
+    /// Record a value.
+    pub fn record(&mut self, value: u64) {
+        let idx = bucket(value);
+        if idx >= self.counts.len() {
+            self.counts.resize(idx + 1, 0);
+        }
+        self.counts[idx] += 1;
+        self.min = if self.count == 0 {
+            value
+        } else {
+            self.min.min(value)
+        };
+        self.max = self.max.max(value);
+        self.count += 1;
+        self.sum = self.sum.saturating_add(value);
+    }
+
+    /// Record a duration, in nanoseconds.
+    pub fn record_duration(&mut self, duration: Duration) {
+        self.record(u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX));
+    }
+
+    /// Return the number of recorded values.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Return the sum of the recorded values, saturating at `u64::MAX`.
+    pub fn sum(&self) -> u64 {
+        self.sum
+    }
+
+    /// Return the smallest recorded value.
+    pub fn min(&self) -> Option<u64> {
+        (self.count > 0).then_some(self.min)
+    }
+
+    /// Return the greatest recorded value.
+    pub fn max(&self) -> Option<u64> {
+        (self.count > 0).then_some(self.max)
+    }
+
+    /// Return the mean of the recorded values.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn mean(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.sum as f64 / self.count as f64)
+    }
+
+    /// Return an upper bound of the `q`-quantile of the recorded values, for `q` in `0.0..=1.0`.
+    ///
+    /// The bound is the greatest value of the bucket holding the quantile, so it exceeds the
+    /// exact quantile by at most 1/16.
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss
+    )]
+    pub fn quantile(&self, q: f64) -> Option<u64> {
+        if self.count == 0 {
+            return None;
+        }
+        let rank = ((q.clamp(0.0, 1.0) * self.count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (low, high, count) in self.buckets() {
+            seen += count;
+            if seen >= rank {
+                return Some(high.clamp(low.max(self.min), self.max));
+            }
+        }
+        Some(self.max)
+    }
+
+    /// Iterate over the non-empty buckets, as `(lowest value, greatest value, count)`.
+    pub fn buckets(&self) -> impl Iterator<Item = (u64, u64, u64)> + '_ {
+        self.counts
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0)
+            .map(|(idx, count)| {
+                let (low, high) = bounds(idx);
+                (low, high, *count)
+            })
+    }
+}
+
+impl AddAssign for Histogram {
+    fn add_assign(&mut self, rhs: Self) {
+        if rhs.count == 0 {
+            return;
+        }
+        if self.counts.len() < rhs.counts.len() {
+            self.counts.resize(rhs.counts.len(), 0);
+        }
+        for (count, rhs) in self.counts.iter_mut().zip(rhs.counts) {
+            *count += rhs;
+        }
+        self.min = if self.count == 0 {
+            rhs.min
+        } else {
+            self.min.min(rhs.min)
+        };
+        self.max = self.max.max(rhs.max);
+        self.count += rhs.count;
+        self.sum = self.sum.saturating_add(rhs.sum);
+    }
+}
+
+impl Sum for Histogram {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::default(), |mut acc, h| {
+            acc += h;
+            acc
+        })
+    }
+}
+
+impl Debug for Histogram {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        f.debug_struct("Histogram")
+            .field("count", &self.count)
+            .field("min", &self.min())
+            .field("p50", &self.quantile(0.5))
+            .field("p99", &self.quantile(0.99))
+            .field("max", &self.max())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Histograms describing the transactions run with a given name.
+///
+/// Times are recorded in nanoseconds.
+///
+/// ```
+/// # use fast_stm::*;
+/// let var = TVar::new(0);
+/// atomically_named("increment", |trans| var.modify(trans, |x| x + 1));
+///
+/// let histograms = TransactionHistograms::by_name();
+/// assert!(histograms[&Some("increment")].attempts.count() >= 1);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TransactionHistograms {
+    /// Number of attempts of each transaction, including the successful one.
+    pub attempts: Histogram,
+    /// Time from the start of the first attempt to the successful commit.
+    pub commit_time: Histogram,
+    /// Time spent parked after calling `retry`, waiting for a variable to change.
+    pub parked_time: Histogram,
+    /// Number of variables read by committed transactions.
+    pub read_set: Histogram,
+    /// Number of variables written by committed transactions.
+    pub write_set: Histogram,
+}
+
+type Histograms = HashMap<Option<&'static str>, TransactionHistograms>;
+
+/// Histograms of a thread, registered so that snapshots can read them.
+struct Local(Arc<Mutex<Histograms>>);
+
+/// Histograms of all running threads.
+static THREADS: Mutex<Vec<Arc<Mutex<Histograms>>>> = Mutex::new(Vec::new());
+
+/// Histograms of the threads that have exited.
+static RETIRED: Mutex<Option<Histograms>> = Mutex::new(None);
+
+thread_local!(static LOCAL: Local = Local::register());
+
+impl Local {
+    fn register() -> Self {
+        let histograms = Arc::default();
+        THREADS.lock().push(Arc::clone(&histograms));
+        Self(histograms)
+    }
+}
+
+impl Drop for Local {
+    fn drop(&mut self) {
+        THREADS.lock().retain(|h| !Arc::ptr_eq(h, &self.0));
+        merge(
+            RETIRED.lock().get_or_insert_with(Histograms::default),
+            std::mem::take(&mut *self.0.lock()),
+        );
+    }
+}
+
+/// Merge histograms grouped by name into `into`.
+fn merge(into: &mut Histograms, from: Histograms) {
+    for (name, histograms) in from {
+        *into.entry(name).or_default() += histograms;
+    }
+}
+
+/// Record into the histograms of the current thread for the transactions named `name`.
+pub(crate) fn record<F>(name: Option<&'static str>, f: F)
+where
+    F: FnOnce(&mut TransactionHistograms),
+{
+    // Histograms are not recorded while the thread is exiting.
+    let _ = LOCAL.try_with(|local| f(local.0.lock().entry(name).or_default()));
+}
+
+impl TransactionHistograms {
+    /// Return the histograms of all threads, grouped by transaction name.
+    ///
+    /// Unnamed transactions are grouped under `None`.
+    pub fn by_name() -> BTreeMap<Option<&'static str>, TransactionHistograms> {
+        let mut all = RETIRED.lock().clone().unwrap_or_default();
+        for local in THREADS.lock().iter() {
+            merge(&mut all, local.lock().clone());
+        }
+        all.into_iter().collect()
+    }
+
+    /// Return the histograms of all transactions of all threads.
+    pub fn total() -> TransactionHistograms {
+        Self::by_name().into_values().sum()
+    }
+
+    /// Clear the histograms of all threads.
+    pub fn reset() {
+        *RETIRED.lock() = None;
+        for local in THREADS.lock().iter() {
+            local.lock().clear();
+        }
+    }
+}
+
+impl AddAssign for TransactionHistograms {
+    fn add_assign(&mut self, rhs: Self) {
+        self.attempts += rhs.attempts;
+        self.commit_time += rhs.commit_time;
+        self.parked_time += rhs.parked_time;
+        self.read_set += rhs.read_set;
+        self.write_set += rhs.write_set;
+    }
+}
+
+impl Sum for TransactionHistograms {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::default(), |mut acc, h| {
+            acc += h;
+            acc
+        })
+    }
+}
+
+/// Tracks the attempts of a transaction, and records them when the transaction ends.
+#[derive(Default)]
+pub(crate) struct Recorder {
+    name: Option<&'static str>,
+    start: Option<Instant>,
+    attempts: u64,
+}
+
+impl Recorder {
+    /// Register the start of an attempt of the transaction `name`.
+    pub(crate) fn attempt(&mut self, name: Option<&'static str>) {
+        self.name = name;
+        self.start.get_or_insert_with(Instant::now);
+        self.attempts += 1;
+    }
+
+    /// Record a successful commit, with the sizes of the read and write sets.
+    pub(crate) fn commit(&self, reads: usize, writes: usize) {
+        record(self.name, |h| {
+            if let Some(start) = self.start {
+                h.commit_time.record_duration(start.elapsed());
+            }
+            h.read_set.record(reads as u64);
+            h.write_set.record(writes as u64);
+        });
+    }
+
+    /// Record the time spent parked by the transaction.
+    #[cfg(feature = "wait-on-retry")]
+    pub(crate) fn parked(&self, duration: Duration) {
+        record(self.name, |h| h.parked_time.record_duration(duration));
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        if self.attempts > 0 {
+            record(self.name, |h| h.attempts.record(self.attempts));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn buckets() {
+        for value in (0..10_000).chain([u64::MAX / 3, u64::MAX - 1, u64::MAX]) {
+            let (low, high) = bounds(bucket(value));
+            assert!(
+                low <= value && value <= high,
+                "{value} not in {low}..={high}"
+            );
+            assert!(
+                high - low <= low / SUB_BUCKETS,
+                "bucket of {value} is too wide"
+            );
+        }
+        assert_eq!(bounds(bucket(u64::MAX)).1, u64::MAX);
+    }
+
+    #[test]
+    fn quantiles_and_merge() {
+        let mut a = Histogram::default();
+        let mut b = Histogram::default();
+        for v in 0..1000 {
+            if v % 2 == 0 {
+                a.record(v);
+            } else {
+                b.record(v);
+            }
+        }
+        let h: Histogram = [a, b].into_iter().sum();
+
+        assert_eq!(h.count(), 1000);
+        assert_eq!(h.min(), Some(0));
+        assert_eq!(h.max(), Some(999));
+        assert_eq!(h.sum(), 999 * 1000 / 2);
+        let p90 = h.quantile(0.9).unwrap();
+        assert!((899..=899 + 899 / 16).contains(&p90), "p90 = {p90}");
+        assert_eq!(h.quantile(1.0), Some(999));
+        assert_eq!(Histogram::default().quantile(0.5), None);
+    }
+
+    /// Histograms of exited threads are kept.
+    #[test]
+    fn threads() {
+        std::thread::spawn(|| record(Some("histogram-test"), |h| h.read_set.record(7)))
+            .join()
+            .unwrap();
+        record(Some("histogram-test"), |h| h.read_set.record(9));
+
+        let h = &TransactionHistograms::by_name()[&Some("histogram-test")];
+        assert_eq!(h.read_set.count(), 2);
+        assert_eq!(h.read_set.max(), Some(9));
+    }
+}
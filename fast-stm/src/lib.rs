@@ -35,9 +35,17 @@
 //!   - this may lead to improved performance if your transactions are longer / read-heavy, due to
 //!     lookup computational complexity
 //!   - the hash algorithm is provided by the `rustc-hash` crate, not the `std`
+//! - `persistent` -- re-export the persistent collections of the `imbl` crate, which can be cloned
+//!   in constant time, and add helper methods to `TVar`s holding them
 //! - `wait-on-retry` -- if `retry` is called explictly in a transaction, the thread will go to
 //!   sleep and wait for one of the variables read in the initial transaction to change before
 //!   re-attempting computation
+//!   - `ParkedTransaction::snapshot` lists the transactions currently waiting, with the
+//!     variables they wait for, once enabled with `ParkedTransaction::track`
+//!   - `TVar::waiter_count` and `TVar::dead_waiter_count` report the state of the wait queue of
+//!     a variable, whose stale entries pile up if it is often waited for but rarely written
+//! - `watchdog` -- add `Watchdog`, which reports transactions exceeding a number of attempts or
+//!   a duration, and transactions parked on variables that nothing else references
 //!
 //! By default, only the `wait-on-retry` feature is enabled, to keep the behavior identical to the
 //! original library.
@@ -48,6 +56,19 @@
 //! be retrieved by passing a reference to `TransactionTallies` to the new entry functions:
 //! `profile_atomically`, ...
 //!
+//! The tallies also record which variables failed validation, so that the variables causing
+//! the most conflicts can be listed with `TransactionTallies::hottest_conflicts`. Variables can be
+//! labeled with `TVar::with_name` to tell them apart in these reports.
+//!
+//! All transactions, including those run with `atomically`, also update process-wide counters.
+//! They can be read and reset at any time with `StmStats::snapshot` and `StmStats::reset`.
+//!
+//! `TransactionHistograms` breaks down the distribution of attempts, commit times, parked times
+//! and read/write set sizes by transaction name (see `atomically_named`).
+//!
+//! `PrometheusExporter` renders these counters and histograms in the Prometheus text exposition
+//! format, into any `fmt::Write` or `io::Write`.
+//!
 //! <div class="warning">
 //!
 //! Do not use the `profiling` feature if you are benchmarking execution times. While regular entry
@@ -57,6 +78,24 @@
 //!
 //! </div>
 //!
+//! ## Tracing
+//!
+//! The `tracing` feature instruments transactions with the `tracing` crate. Each call to
+//! `atomically` (or any other entry function) is covered by an `atomically` span holding the
+//! name of the transaction, and each run of the closure by a nested `attempt` span. Events are
+//! emitted for commits, failed validations with the variable that changed, calls to `retry` with
+//! the time spent parked, aborts, branch switches in `Transaction::or`, and wake-ups of waiting
+//! transactions. Without the feature, no instrumentation is compiled in.
+//!
+//! ## Event log
+//!
+//! The `event-log` feature adds `EventLog`, which records every attempt of every transaction
+//! to a JSON-lines file: the variables read and written, with their values for the types
+//! registered with `EventLog::render`, the outcome of the attempt and its timing. Commits are
+//! numbered in the order they take effect. The `stm-log` binary of the repository reads these
+//! files back, lists the commits in serialization order, and points at the commits that caused
+//! each conflict.
+//!
 //! # Usage
 //!
 //! You should only use the functions that are transaction-safe.
@@ -161,20 +200,61 @@
 
 extern crate parking_lot;
 
+mod collections;
+#[cfg(feature = "event-log")]
+mod event_log;
+#[cfg(feature = "profiling")]
+mod histogram;
+#[cfg(feature = "wait-on-retry")]
+mod parked;
+#[cfg(feature = "persistent")]
+mod persistent;
+#[cfg(feature = "profiling")]
+mod prometheus;
 mod result;
+#[cfg(feature = "profiling")]
+mod stats;
+mod sync;
+mod tarray;
 mod transaction;
 mod tvar;
+#[cfg(feature = "watchdog")]
+mod watchdog;
 
 #[cfg(test)]
 mod test;
 
+pub use collections::{
+    ListIter, TCounter, TDeque, THashMap, TList, TPriorityQueue, TRingBuffer, TSet, TSkipList,
+};
 pub use result::*;
+pub use sync::{
+    TBarrier, TCountDownLatch, TEvent, TMutex, TMutexGuard, TOnceCell, TRwLock, TRwLockReadGuard,
+    TRwLockWriteGuard,
+};
+pub use tarray::TArray;
 pub use transaction::Transaction;
 pub use transaction::TransactionControl;
 pub use tvar::TVar;
 
+#[cfg(feature = "event-log")]
+pub use event_log::EventLog;
+#[cfg(feature = "profiling")]
+pub use histogram::{Histogram, TransactionHistograms};
+#[cfg(feature = "wait-on-retry")]
+pub use parked::ParkedTransaction;
+#[cfg(feature = "persistent")]
+pub use persistent::{PMap, POrdMap, PSet, PVec};
+#[cfg(feature = "profiling")]
+pub use prometheus::PrometheusExporter;
+#[cfg(feature = "profiling")]
+pub use stats::StmStats;
 #[cfg(feature = "profiling")]
 pub use transaction::TransactionTallies;
+#[cfg(diagnostics)]
+pub use tvar::VarId;
+#[cfg(feature = "watchdog")]
+pub use watchdog::{Watchdog, WatchdogAlert, WatchdogReport};
 
 /// Convert a `TransactionClosureResult<T, E_A>` to `TransactionClosureResult<T, E_B>`.
 ///
@@ -277,6 +357,30 @@ where
     Transaction::with(f)
 }
 
+/// Run a function atomically, in a transaction labeled `name`.
+///
+/// The label identifies the transaction in diagnostics, such as the panic raised by nested
+/// transactions. It calls to `Transaction::with_name` internally.
+///
+/// ```
+/// # use fast_stm::*;
+/// let from = TVar::new(10);
+/// let to = TVar::new(0);
+///
+/// atomically_named("transfer", |trans| {
+///     from.modify(trans, |x| x - 5)?;
+///     to.modify(trans, |x| x + 5)
+/// });
+///
+/// assert_eq!(to.read_atomic(), 5);
+/// ```
+pub fn atomically_named<T, F>(name: &'static str, f: F) -> T
+where
+    F: Fn(&mut Transaction) -> StmClosureResult<T>,
+{
+    Transaction::with_name(name, f)
+}
+
 /// Run a function atomically by using Software Transactional Memory.
 /// It calls to `Transaction::with_err` internally, but is more explicit.
 pub fn atomically_with_err<T, E, F>(f: F) -> Result<T, E>
@@ -0,0 +1,166 @@
+//! Registry of the transactions parked on `retry`.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::thread::{self, Thread, ThreadId};
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+
+use crate::tvar::{VarId, VarRef};
+
+/// Whether parked transactions are registered.
+static TRACKING: AtomicBool = AtomicBool::new(false);
+
+/// Transactions currently parked, by registration number.
+static PARKED: Mutex<BTreeMap<u64, Entry>> = Mutex::new(BTreeMap::new());
+
+struct Entry {
+    name: Option<&'static str>,
+    thread: Thread,
+    since: Instant,
+    vars: Vec<VarId>,
+}
+
+/// A transaction parked on `retry`, waiting for one of the variables it read to change.
+///
+/// Parked transactions are only registered after a call to `track`, so that programs not
+/// inspecting them do not pay for the registration.
+///
+/// ```
+/// # use fast_stm::*;
+/// # use std::time::Duration;
+/// ParkedTransaction::track(true);
+///
+/// for parked in ParkedTransaction::snapshot() {
+///     if parked.parked > Duration::from_secs(60) {
+///         eprintln!("{:?} is stuck on {:?}", parked.name, parked.vars);
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ParkedTransaction {
+    /// Name of the transaction, if any.
+    pub name: Option<&'static str>,
+    /// Thread running the transaction.
+    pub thread: ThreadId,
+    /// Name of the thread running the transaction, if any.
+    pub thread_name: Option<String>,
+    /// Time spent parked so far.
+    pub parked: Duration,
+    /// Variables the transaction waits for.
+    pub vars: Vec<VarId>,
+}
+
+impl ParkedTransaction {
+    /// Start or stop registering the transactions that get parked.
+    ///
+    /// Transactions that were already parked when tracking started are not listed.
+    pub fn track(enabled: bool) {
+        TRACKING.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Return the transactions currently parked, longest parked first.
+    pub fn snapshot() -> Vec<ParkedTransaction> {
+        let now = Instant::now();
+        // Entries are registered in order, so the oldest ones come first.
+        PARKED
+            .lock()
+            .values()
+            .map(|entry| ParkedTransaction {
+                name: entry.name,
+                thread: entry.thread.id(),
+                thread_name: entry.thread.name().map(String::from),
+                parked: now.saturating_duration_since(entry.since),
+                vars: entry.vars.clone(),
+            })
+            .collect()
+    }
+}
+
+/// Registration of the current transaction as parked, removed when dropped.
+pub(crate) struct Parking(Option<u64>);
+
+impl Parking {
+    /// Register the transaction `name` as parked until one of `vars` changes, if parked
+    /// transactions are tracked.
+    pub(crate) fn new(name: Option<&'static str>, vars: &[VarRef]) -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        if !TRACKING.load(Ordering::Relaxed) {
+            return Self(None);
+        }
+        let id = NEXT.fetch_add(1, Ordering::Relaxed);
+        let entry = Entry {
+            name,
+            thread: thread::current(),
+            since: Instant::now(),
+            vars: vars.iter().map(VarRef::id).collect(),
+        };
+        PARKED.lock().insert(id, entry);
+        Self(Some(id))
+    }
+}
+
+impl Drop for Parking {
+    fn drop(&mut self) {
+        if let Some(id) = self.0 {
+            PARKED.lock().remove(&id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{atomically, guard, test, TVar, Transaction};
+
+    /// Wait for the transaction `name` to be parked, returning its entry.
+    fn wait_parked(name: &str) -> ParkedTransaction {
+        loop {
+            let parked = ParkedTransaction::snapshot()
+                .into_iter()
+                .find(|parked| parked.name == Some(name));
+            if let Some(parked) = parked {
+                return parked;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn parked_and_stale_waiters() {
+        ParkedTransaction::track(true);
+        let a = TVar::with_name(0, "a");
+        let b = TVar::with_name(0, "b");
+        let (ac, bc) = (a.clone(), b.clone());
+
+        let terminated = test::terminates_async(
+            800,
+            move || {
+                Transaction::with_name("parked-test", |trans| {
+                    ac.read(trans)?;
+                    guard(bc.read(trans)? != 0)
+                });
+            },
+            || {
+                let parked = wait_parked("parked-test");
+                let mut names: Vec<_> = parked.vars.iter().map(VarId::name).collect();
+                names.sort_unstable();
+                assert_eq!(names, [Some("a"), Some("b")]);
+                assert_eq!(a.waiter_count(), 1);
+                assert_eq!(a.dead_waiter_count(), 0);
+
+                atomically(|trans| b.write(trans, 1));
+            },
+        );
+        assert!(terminated);
+
+        // the entry of `a` is left behind, since `a` did not change
+        assert_eq!(a.waiter_count(), 0);
+        assert_eq!(a.dead_waiter_count(), 1);
+        assert_eq!(b.dead_waiter_count(), 0);
+        assert!(ParkedTransaction::snapshot()
+            .iter()
+            .all(|parked| parked.name != Some("parked-test")));
+    }
+}
@@ -0,0 +1,285 @@
+//! Integration of persistent collections, provided by the `imbl` crate.
+//!
+//! Reading a `TVar` clones its value, and so does modifying it. For large collections such as
+//! `Vec` or `HashMap`, this makes every access linear in the size of the collection. The
+//! persistent collections re-exported here share their structure between versions: cloning
+//! them is O(1), and updating them only copies O(log n) nodes.
+//!
+//! `TVar`s holding these collections get helper methods performing the update in a single
+//! access to the variable. Note that the whole collection is still a single variable: any
+//! modification conflicts with any other access.
+//!
+//! ```
+//! # use fast_stm::*;
+//! let queue: TVar<PVec<u32>> = TVar::new(PVec::new());
+//! let index: TVar<PMap<&str, u32>> = TVar::new(PMap::new());
+//!
+//! atomically(|trans| {
+//!     queue.push_back(trans, 42)?;
+//!     index.insert(trans, "answer", 42)?;
+//!     Ok(())
+//! });
+//!
+//! assert_eq!(atomically(|trans| queue.pop_front(trans)), Some(42));
+//! assert_eq!(atomically(|trans| index.get(trans, &"answer")), Some(42));
+//! ```
+
+use std::any::Any;
+use std::borrow::Borrow;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash};
+
+use imbl::shared_ptr::DefaultSharedPtr;
+
+use crate::{StmClosureResult, TVar, Transaction};
+
+/// A persistent vector, implemented as a RRB tree.
+pub type PVec<T> = imbl::Vector<T>;
+
+/// A persistent hash map, implemented as a HAMT.
+///
+/// Unlike `std::collections::HashMap`, `new` is generic over the hasher: name the type, e.g.
+/// `PMap<K, V>`, if the hasher cannot be inferred.
+pub type PMap<K, V, S = RandomState> = imbl::GenericHashMap<K, V, S, DefaultSharedPtr>;
+
+/// A persistent hash set, implemented as a HAMT.
+///
+/// Like [`PMap`], name the type if the hasher cannot be inferred.
+pub type PSet<T, S = RandomState> = imbl::GenericHashSet<T, S, DefaultSharedPtr>;
+
+/// A persistent ordered map, implemented as a B-tree.
+pub type POrdMap<K, V> = imbl::OrdMap<K, V>;
+
+/// Update the value of `var` in place with `f`, and return the output of `f`.
+fn update<T, R, F>(var: &TVar<T>, transaction: &mut Transaction, f: F) -> StmClosureResult<R>
+where
+    T: Any + Send + Sync + Clone,
+    F: FnOnce(&mut T) -> R,
+{
+    let mut output = None;
+    var.modify(transaction, |mut value| {
+        output = Some(f(&mut value));
+        value
+    })?;
+    Ok(output.expect("modify did not call its closure"))
+}
+
+impl<T> TVar<PVec<T>>
+where
+    T: Any + Send + Sync + Clone,
+{
+    /// Push an element at the back of the vector.
+    pub fn push_back(&self, transaction: &mut Transaction, value: T) -> StmClosureResult<()> {
+        update(self, transaction, |vec| vec.push_back(value))
+    }
+
+    /// Push an element at the front of the vector.
+    pub fn push_front(&self, transaction: &mut Transaction, value: T) -> StmClosureResult<()> {
+        update(self, transaction, |vec| vec.push_front(value))
+    }
+
+    /// Remove the last element of the vector and return it, or `None` if it is empty.
+    pub fn pop_back(&self, transaction: &mut Transaction) -> StmClosureResult<Option<T>> {
+        update(self, transaction, PVec::pop_back)
+    }
+
+    /// Remove the first element of the vector and return it, or `None` if it is empty.
+    pub fn pop_front(&self, transaction: &mut Transaction) -> StmClosureResult<Option<T>> {
+        update(self, transaction, PVec::pop_front)
+    }
+
+    /// Return the element at `index`, if any.
+    pub fn get(&self, transaction: &mut Transaction, index: usize) -> StmClosureResult<Option<T>> {
+        Ok(self.read(transaction)?.get(index).cloned())
+    }
+
+    /// Return the number of elements of the vector.
+    pub fn len(&self, transaction: &mut Transaction) -> StmClosureResult<usize> {
+        Ok(self.read(transaction)?.len())
+    }
+
+    /// Check if the vector is empty.
+    pub fn is_empty(&self, transaction: &mut Transaction) -> StmClosureResult<bool> {
+        Ok(self.read(transaction)?.is_empty())
+    }
+}
+
+impl<K, V, S> TVar<PMap<K, V, S>>
+where
+    K: Any + Send + Sync + Clone + Hash + Eq,
+    V: Any + Send + Sync + Clone,
+    S: Any + Send + Sync + BuildHasher + Clone,
+{
+    /// Insert a key-value pair into the map, returning the previous value of the key.
+    pub fn insert(
+        &self,
+        transaction: &mut Transaction,
+        key: K,
+        value: V,
+    ) -> StmClosureResult<Option<V>> {
+        update(self, transaction, |map| map.insert(key, value))
+    }
+
+    /// Remove a key from the map, returning its value.
+    pub fn remove<Q>(&self, transaction: &mut Transaction, key: &Q) -> StmClosureResult<Option<V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        update(self, transaction, |map| map.remove(key))
+    }
+
+    /// Return the value associated to `key`, if any.
+    pub fn get<Q>(&self, transaction: &mut Transaction, key: &Q) -> StmClosureResult<Option<V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        Ok(self.read(transaction)?.get(key).cloned())
+    }
+
+    /// Check if the map contains `key`.
+    pub fn contains_key<Q>(&self, transaction: &mut Transaction, key: &Q) -> StmClosureResult<bool>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        Ok(self.read(transaction)?.contains_key(key))
+    }
+}
+
+impl<T, S> TVar<PSet<T, S>>
+where
+    T: Any + Send + Sync + Clone + Hash + Eq,
+    S: Any + Send + Sync + BuildHasher + Clone,
+{
+    /// Insert a value into the set.
+    ///
+    /// Return `true` if the value was not already present.
+    pub fn insert(&self, transaction: &mut Transaction, value: T) -> StmClosureResult<bool> {
+        update(self, transaction, |set| set.insert(value).is_none())
+    }
+
+    /// Remove a value from the set.
+    ///
+    /// Return `true` if the value was present.
+    pub fn remove<Q>(&self, transaction: &mut Transaction, value: &Q) -> StmClosureResult<bool>
+    where
+        T: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        update(self, transaction, |set| set.remove(value).is_some())
+    }
+
+    /// Check if the set contains `value`.
+    pub fn contains<Q>(&self, transaction: &mut Transaction, value: &Q) -> StmClosureResult<bool>
+    where
+        T: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        Ok(self.read(transaction)?.contains(value))
+    }
+}
+
+impl<K, V> TVar<POrdMap<K, V>>
+where
+    K: Any + Send + Sync + Clone + Ord,
+    V: Any + Send + Sync + Clone,
+{
+    /// Insert a key-value pair into the map, returning the previous value of the key.
+    pub fn insert(
+        &self,
+        transaction: &mut Transaction,
+        key: K,
+        value: V,
+    ) -> StmClosureResult<Option<V>> {
+        update(self, transaction, |map| map.insert(key, value))
+    }
+
+    /// Remove a key from the map, returning its value.
+    pub fn remove<Q>(&self, transaction: &mut Transaction, key: &Q) -> StmClosureResult<Option<V>>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        update(self, transaction, |map| map.remove(key))
+    }
+
+    /// Return the value associated to `key`, if any.
+    pub fn get<Q>(&self, transaction: &mut Transaction, key: &Q) -> StmClosureResult<Option<V>>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        Ok(self.read(transaction)?.get(key).cloned())
+    }
+
+    /// Check if the map contains `key`.
+    pub fn contains_key<Q>(&self, transaction: &mut Transaction, key: &Q) -> StmClosureResult<bool>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        Ok(self.read(transaction)?.contains_key(key))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::atomically;
+
+    #[test]
+    fn vector() {
+        let var = TVar::new(PVec::new());
+
+        atomically(|tx| {
+            var.push_back(tx, 2)?;
+            var.push_front(tx, 1)?;
+            var.push_back(tx, 3)
+        });
+        assert_eq!(atomically(|tx| var.len(tx)), 3);
+        assert_eq!(atomically(|tx| var.get(tx, 1)), Some(2));
+
+        assert_eq!(atomically(|tx| var.pop_front(tx)), Some(1));
+        assert_eq!(atomically(|tx| var.pop_back(tx)), Some(3));
+        assert_eq!(var.read_atomic(), PVec::unit(2));
+    }
+
+    #[test]
+    fn maps_and_sets() {
+        let map: TVar<PMap<_, _>> = TVar::new(PMap::new());
+        let ord_map = TVar::new(POrdMap::new());
+        let set: TVar<PSet<_>> = TVar::new(PSet::new());
+
+        atomically(|tx| {
+            assert_eq!(map.insert(tx, "a", 1)?, None);
+            assert_eq!(map.insert(tx, "a", 2)?, Some(1));
+            assert_eq!(ord_map.insert(tx, 2, "b")?, None);
+            assert!(set.insert(tx, 'x')?);
+            assert!(!set.insert(tx, 'x')?);
+            Ok(())
+        });
+
+        assert_eq!(atomically(|tx| map.get(tx, "a")), Some(2));
+        assert!(atomically(|tx| ord_map.contains_key(tx, &2)));
+        assert!(atomically(|tx| set.contains(tx, &'x')));
+
+        assert_eq!(atomically(|tx| map.remove(tx, "a")), Some(2));
+        assert_eq!(atomically(|tx| ord_map.remove(tx, &2)), Some("b"));
+        assert!(atomically(|tx| set.remove(tx, &'x')));
+        assert!(!atomically(|tx| map.contains_key(tx, "a")));
+    }
+
+    /// An aborted update leaves the shared collection untouched.
+    #[test]
+    fn aborted_update() {
+        let var = TVar::new((0..1000).collect::<PVec<_>>());
+
+        let mut tx = Transaction::default();
+        var.push_back(&mut tx, 1000).unwrap();
+        drop(tx);
+
+        assert_eq!(var.read_atomic().len(), 1000);
+    }
+}
@@ -0,0 +1,432 @@
+//! Rendering of the `profiling` metrics in the Prometheus text exposition format.
+//!
+//! The renderer only produces text: serving it, e.g. on a `/metrics` endpoint, is left to the
+//! application.
+
+#[cfg(feature = "wait-on-retry")]
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::fmt::{self, Write};
+use std::io;
+
+use crate::histogram::{Histogram, TransactionHistograms};
+use crate::stats::StmStats;
+#[cfg(feature = "wait-on-retry")]
+use crate::tvar::{TVar, VarRef};
+
+/// Renders the process-wide STM statistics in the Prometheus text exposition format.
+///
+/// The rendered metrics are:
+///
+/// - the counters of `StmStats`, with failed attempts counted in `fast_stm_aborts_total` and
+///   labeled by reason (`conflict`, `retry`, `early_conflict` or `failure`),
+/// - the histograms of `TransactionHistograms`, labeled by transaction name, with buckets at the
+///   powers of two up to 1024 for sizes and counts, and at the powers of ten from 1µs to 10s for
+///   durations,
+/// - with the `wait-on-retry` feature, the number of threads waiting for each variable passed to
+///   `watch`.
+///
+/// ```
+/// # use fast_stm::*;
+/// let balance = TVar::new(0);
+/// atomically_named("deposit", |trans| balance.modify(trans, |x| x + 10));
+///
+/// let exporter = PrometheusExporter::new();
+/// let mut text = String::new();
+/// exporter.render_fmt(&mut text).unwrap();
+///
+/// assert!(text.contains("fast_stm_commits_total"));
+/// assert!(text.contains(r#"fast_stm_transaction_attempts_count{transaction="deposit"}"#));
+/// ```
+#[derive(Default)]
+pub struct PrometheusExporter {
+    /// Variables whose waiting threads are reported.
+    #[cfg(feature = "wait-on-retry")]
+    vars: Vec<VarRef>,
+}
+
+impl PrometheusExporter {
+    /// Create an exporter rendering the global metrics.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Report the number of threads waiting for a change of `var`.
+    ///
+    /// The variable is labeled with its name and address, see `VarId`. The exporter keeps a
+    /// handle to the variable, so the variable is not dropped before the exporter.
+    ///
+    /// ```
+    /// # use fast_stm::*;
+    /// let balance = TVar::with_name(0, "balance");
+    /// let exporter = PrometheusExporter::new().watch(&balance);
+    ///
+    /// let mut text = String::new();
+    /// exporter.render_fmt(&mut text).unwrap();
+    /// assert!(text.contains("fast_stm_waiting_threads{var=\"balance@"));
+    /// ```
+    #[cfg(feature = "wait-on-retry")]
+    #[must_use]
+    pub fn watch<T>(mut self, var: &TVar<T>) -> Self
+    where
+        T: Any + Send + Sync,
+    {
+        self.vars.push(var.var_ref());
+        self
+    }
+
+    /// Render the current metrics into `out`.
+    pub fn render_fmt<W: Write>(&self, out: &mut W) -> fmt::Result {
+        self.metrics().render(out)
+    }
+
+    /// Render the current metrics into `out`.
+    pub fn render_io<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
+        let mut text = String::new();
+        self.metrics()
+            .render(&mut text)
+            .map_err(|_| io::Error::other("failed to format metrics"))?;
+        out.write_all(text.as_bytes())
+    }
+
+    /// Take a snapshot of the metrics to render.
+    #[cfg_attr(not(feature = "wait-on-retry"), allow(clippy::unused_self))]
+    fn metrics(&self) -> Metrics {
+        Metrics {
+            stats: StmStats::snapshot(),
+            histograms: TransactionHistograms::by_name(),
+            #[cfg(feature = "wait-on-retry")]
+            waiting: self
+                .vars
+                .iter()
+                .map(|var| (var.id().to_string(), var.waiters().waiting()))
+                .collect(),
+            #[cfg(not(feature = "wait-on-retry"))]
+            waiting: Vec::new(),
+        }
+    }
+}
+
+/// A snapshot of the metrics, rendered independently of the global state.
+struct Metrics {
+    stats: StmStats,
+    histograms: BTreeMap<Option<&'static str>, TransactionHistograms>,
+    /// Number of waiting threads, for each label of a watched variable.
+    waiting: Vec<(String, usize)>,
+}
+
+/// Unit of the values of a histogram.
+#[derive(Clone, Copy)]
+enum Unit {
+    /// Plain numbers.
+    Count,
+    /// Nanoseconds, rendered as seconds.
+    Nanos,
+}
+
+impl Unit {
+    /// Return the upper bounds of the buckets rendered for the histograms of this unit.
+    ///
+    /// The set is fixed, so that every histogram has the same series whatever its values.
+    fn bounds(self) -> &'static [u64] {
+        match self {
+            Self::Count => &[1, 2, 4, 8, 16, 32, 64, 128, 256, 512, 1024],
+            Self::Nanos => &[
+                1_000,
+                10_000,
+                100_000,
+                1_000_000,
+                10_000_000,
+                100_000_000,
+                1_000_000_000,
+                10_000_000_000,
+            ],
+        }
+    }
+}
+
+impl Metrics {
+    fn render<W: Write>(&self, out: &mut W) -> fmt::Result {
+        let stats = &self.stats;
+        counter(
+            out,
+            "attempts",
+            "Runs of transaction closures.",
+            stats.attempts,
+        )?;
+        counter(out, "commits", "Successful commits.", stats.commits)?;
+
+        header(
+            out,
+            "aborts_total",
+            "counter",
+            "Failed attempts, by reason.",
+        )?;
+        for (reason, value) in [
+            ("conflict", stats.commit_failures),
+            ("retry", stats.retries),
+            ("early_conflict", stats.early_conflicts),
+            ("failure", stats.failures),
+        ] {
+            writeln!(out, "fast_stm_aborts_total{{reason=\"{reason}\"}} {value}")?;
+        }
+
+        counter(
+            out,
+            "waits",
+            "Threads parked, waiting for a variable to change.",
+            stats.waits,
+        )?;
+        counter(
+            out,
+            "wakeups",
+            "Parked threads woken up by a commit.",
+            stats.wakeups,
+        )?;
+        counter(
+            out,
+            "dead_waiter_collections",
+            "Collections of dead waiters in wait queues.",
+            stats.dead_waiter_collections,
+        )?;
+
+        if !self.waiting.is_empty() {
+            header(
+                out,
+                "waiting_threads",
+                "gauge",
+                "Threads waiting for a change of a variable.",
+            )?;
+            for (var, count) in &self.waiting {
+                writeln!(
+                    out,
+                    "fast_stm_waiting_threads{{var=\"{}\"}} {count}",
+                    Escaped(var)
+                )?;
+            }
+        }
+
+        self.histogram(
+            out,
+            "transaction_attempts",
+            "Attempts of each transaction.",
+            Unit::Count,
+            |h| &h.attempts,
+        )?;
+        self.histogram(
+            out,
+            "transaction_commit_seconds",
+            "Time from the first attempt to the commit.",
+            Unit::Nanos,
+            |h| &h.commit_time,
+        )?;
+        self.histogram(
+            out,
+            "transaction_parked_seconds",
+            "Time parked after calling retry.",
+            Unit::Nanos,
+            |h| &h.parked_time,
+        )?;
+        self.histogram(
+            out,
+            "transaction_read_set_size",
+            "Variables read by committed transactions.",
+            Unit::Count,
+            |h| &h.read_set,
+        )?;
+        self.histogram(
+            out,
+            "transaction_write_set_size",
+            "Variables written by committed transactions.",
+            Unit::Count,
+            |h| &h.write_set,
+        )
+    }
+
+    /// Render the histogram selected by `select`, for each transaction name.
+    ///
+    /// The buckets of the histogram are folded into the fixed bounds of `unit`: each one is
+    /// counted in the first bound at or above its greatest value.
+    fn histogram<W, F>(
+        &self,
+        out: &mut W,
+        name: &str,
+        help: &str,
+        unit: Unit,
+        select: F,
+    ) -> fmt::Result
+    where
+        W: Write,
+        F: Fn(&TransactionHistograms) -> &Histogram,
+    {
+        header(out, name, "histogram", help)?;
+        for (transaction, histograms) in &self.histograms {
+            let histogram = select(histograms);
+            let label = match transaction {
+                Some(transaction) => format!("transaction=\"{}\",", Escaped(transaction)),
+                None => String::new(),
+            };
+            let mut buckets = histogram.buckets().peekable();
+            let mut cumulative = 0;
+            for &bound in unit.bounds() {
+                while let Some((_, _, count)) = buckets.next_if(|(_, high, _)| *high <= bound) {
+                    cumulative += count;
+                }
+                writeln!(
+                    out,
+                    "fast_stm_{name}_bucket{{{label}le=\"{}\"}} {cumulative}",
+                    Value(bound, unit)
+                )?;
+            }
+            writeln!(
+                out,
+                "fast_stm_{name}_bucket{{{label}le=\"+Inf\"}} {}",
+                histogram.count()
+            )?;
+            let label = label.trim_end_matches(',');
+            let label = if label.is_empty() {
+                String::new()
+            } else {
+                format!("{{{label}}}")
+            };
+            writeln!(
+                out,
+                "fast_stm_{name}_sum{label} {}",
+                Value(histogram.sum(), unit)
+            )?;
+            writeln!(out, "fast_stm_{name}_count{label} {}", histogram.count())?;
+        }
+        Ok(())
+    }
+}
+
+/// Render the `HELP` and `TYPE` lines of the metric family `name`.
+fn header<W: Write>(out: &mut W, name: &str, kind: &str, help: &str) -> fmt::Result {
+    writeln!(out, "# HELP fast_stm_{name} {help}")?;
+    writeln!(out, "# TYPE fast_stm_{name} {kind}")
+}
+
+/// Render a counter without labels.
+fn counter<W: Write>(out: &mut W, name: &str, help: &str, value: u64) -> fmt::Result {
+    header(out, &format!("{name}_total"), "counter", help)?;
+    writeln!(out, "fast_stm_{name}_total {value}")
+}
+
+/// A value of a histogram, formatted in the base unit of its metric.
+struct Value(u64, Unit);
+
+impl fmt::Display for Value {
+    #[allow(clippy::cast_precision_loss)]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.1 {
+            Unit::Count => write!(f, "{}", self.0),
+            Unit::Nanos => write!(f, "{}", self.0 as f64 / 1e9),
+        }
+    }
+}
+
+/// A label value, with backslashes, quotes and line feeds escaped.
+struct Escaped<'a>(&'a str);
+
+impl fmt::Display for Escaped<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for c in self.0.chars() {
+            match c {
+                '\\' => f.write_str("\\\\")?,
+                '"' => f.write_str("\\\"")?,
+                '\n' => f.write_str("\\n")?,
+                c => f.write_char(c)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Compare `metrics` rendered with the golden file `name`.
+    ///
+    /// Set `UPDATE_GOLDEN=1` to rewrite the golden file instead.
+    fn check_golden(name: &str, metrics: &Metrics) {
+        let path = format!(
+            "{}/testdata/prometheus/{name}.prom",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let mut text = String::new();
+        metrics.render(&mut text).unwrap();
+
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            std::fs::write(&path, &text).unwrap();
+        }
+        let golden = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(text, golden, "rendered metrics differ from {path}");
+    }
+
+    #[test]
+    fn golden_empty() {
+        let metrics = Metrics {
+            stats: StmStats::default(),
+            histograms: BTreeMap::new(),
+            waiting: Vec::new(),
+        };
+        check_golden("empty", &metrics);
+    }
+
+    #[test]
+    fn golden_metrics() {
+        let mut named = TransactionHistograms::default();
+        for attempts in [1, 1, 1, 2, 5] {
+            named.attempts.record(attempts);
+        }
+        for nanos in [1_500, 2_000, 250_000] {
+            named.commit_time.record(nanos);
+        }
+        named.parked_time.record(1_000_000);
+        named.read_set.record(3);
+        named.write_set.record(20);
+
+        let mut unnamed = TransactionHistograms::default();
+        unnamed.attempts.record(1);
+
+        let metrics = Metrics {
+            stats: StmStats {
+                attempts: 12,
+                commits: 6,
+                commit_failures: 4,
+                retries: 2,
+                early_conflicts: 1,
+                failures: 1,
+                waits: 2,
+                wakeups: 1,
+                dead_waiter_collections: 0,
+            },
+            histograms: [(None, unnamed), (Some("say \"hi\""), named)].into(),
+            waiting: vec![(String::from("balance@0x1000"), 2)],
+        };
+        check_golden("metrics", &metrics);
+    }
+
+    /// Both renderers produce the same text.
+    #[test]
+    fn render_io() {
+        let exporter = PrometheusExporter::new();
+        let mut bytes = Vec::new();
+        exporter.render_io(&mut bytes).unwrap();
+        let mut text = String::new();
+        exporter.render_fmt(&mut text).unwrap();
+
+        // Other tests may update the global metrics in between, so only compare the families.
+        let families = |text: &str| -> Vec<String> {
+            text.lines()
+                .filter(|line| line.starts_with("# TYPE"))
+                .map(String::from)
+                .collect()
+        };
+        assert_eq!(
+            families(std::str::from_utf8(&bytes).unwrap()),
+            families(&text)
+        );
+    }
+}
@@ -15,6 +15,59 @@ pub enum StmError {
     Retry,
 }
 
+/// Reason why an attempt of a transaction did not commit.
+///
+/// It is passed to the `control` function of `Transaction::with_control` and the other entry
+/// points taking one, so that policies can react differently to each cause.
+///
+/// ```
+/// # use fast_stm::*;
+/// let var = TVar::new(0);
+/// let mut conflicts = 0;
+///
+/// let x = Transaction::with_control(
+///     |reason| match reason {
+///         FailureReason::CommitConflict if conflicts < 10 => {
+///             conflicts += 1;
+///             TransactionControl::Retry
+///         }
+///         _ => TransactionControl::Abort,
+///     },
+///     |trans| var.read(trans),
+/// );
+/// assert_eq!(x, Some(0));
+/// ```
+#[non_exhaustive]
+#[derive(Eq, PartialEq, Clone, Copy, Debug, thiserror::Error)]
+pub enum FailureReason {
+    /// `retry` was called.
+    #[error("Transaction called retry")]
+    Retry,
+
+    /// A variable read earlier in the attempt changed, as detected when accessing it again with
+    /// the `early-conflict-detection` feature.
+    #[error("Transaction read a variable that has changed")]
+    EarlyConflict,
+
+    /// A variable read by the attempt changed before the commit, so validation failed.
+    #[error("Transaction failed validation at commit")]
+    CommitConflict,
+
+    /// The transaction body returned `StmError::Failure` for another reason.
+    #[error("Transaction failure signal")]
+    Failure,
+}
+
+impl FailureReason {
+    /// Return the `StmError` that would be reported for this reason by a transaction body.
+    pub fn error(self) -> StmError {
+        match self {
+            Self::Retry => StmError::Retry,
+            Self::EarlyConflict | Self::CommitConflict | Self::Failure => StmError::Failure,
+        }
+    }
+}
+
 /// Return type of a transaction body.
 ///
 /// It is the result of a single step of a STM calculation. It informs of success or the type
@@ -0,0 +1,224 @@
+//! Process-wide statistics, aggregated over the transactions of all threads.
+//!
+//! Unlike `TransactionTallies`, which are returned by the `profile_with*` functions, these
+//! counters are updated by every transaction, so they can be read without changing call sites.
+
+use std::ops::Sub;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+/// Number of stripes of each counter.
+const N_STRIPES: usize = 16;
+
+/// A part of a counter, alone on its cache line.
+#[repr(align(64))]
+struct Stripe(AtomicU64);
+
+/// A counter split into stripes, so that threads incrementing it concurrently do not contend on
+/// the same cache line.
+pub(crate) struct StripedCounter {
+    stripes: [Stripe; N_STRIPES],
+}
+
+impl StripedCounter {
+    const fn new() -> Self {
+        Self {
+            stripes: [const { Stripe(AtomicU64::new(0)) }; N_STRIPES],
+        }
+    }
+
+    /// Increment the counter by one.
+    pub(crate) fn incr(&self) {
+        self.stripes[stripe()].0.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Return the value of the counter.
+    fn get(&self) -> u64 {
+        self.stripes
+            .iter()
+            .map(|s| s.0.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    /// Reset the counter, returning its value.
+    fn take(&self) -> u64 {
+        self.stripes
+            .iter()
+            .map(|s| s.0.swap(0, Ordering::Relaxed))
+            .sum()
+    }
+}
+
+/// Return the stripe used by the current thread.
+fn stripe() -> usize {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    thread_local!(static STRIPE: usize = NEXT.fetch_add(1, Ordering::Relaxed) % N_STRIPES);
+    STRIPE.with(|s| *s)
+}
+
+/// The global counters.
+pub(crate) struct Registry {
+    pub(crate) attempts: StripedCounter,
+    pub(crate) commits: StripedCounter,
+    pub(crate) commit_failures: StripedCounter,
+    pub(crate) retries: StripedCounter,
+    pub(crate) early_conflicts: StripedCounter,
+    pub(crate) failures: StripedCounter,
+    pub(crate) waits: StripedCounter,
+    pub(crate) wakeups: StripedCounter,
+    pub(crate) dead_waiter_collections: StripedCounter,
+}
+
+pub(crate) static STATS: Registry = Registry {
+    attempts: StripedCounter::new(),
+    commits: StripedCounter::new(),
+    commit_failures: StripedCounter::new(),
+    retries: StripedCounter::new(),
+    early_conflicts: StripedCounter::new(),
+    failures: StripedCounter::new(),
+    waits: StripedCounter::new(),
+    wakeups: StripedCounter::new(),
+    dead_waiter_collections: StripedCounter::new(),
+};
+
+/// Snapshot of the process-wide STM statistics.
+///
+/// More counters may be added in the future, so the struct is non-exhaustive: get one from
+/// `snapshot`, `reset` or `default`.
+///
+/// ```
+/// # use fast_stm::*;
+/// let var = TVar::new(0);
+/// let before = StmStats::snapshot();
+///
+/// atomically(|trans| var.modify(trans, |x| x + 1));
+///
+/// let delta = StmStats::snapshot() - before;
+/// assert!(delta.commits >= 1);
+/// ```
+#[non_exhaustive]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct StmStats {
+    /// Number of runs of transaction closures.
+    pub attempts: u64,
+    /// Number of successful commits.
+    pub commits: u64,
+    /// Number of commits that failed validation.
+    pub commit_failures: u64,
+    /// Number of attempts that called `retry`.
+    pub retries: u64,
+    /// Number of attempts stopped by the `early-conflict-detection` feature.
+    pub early_conflicts: u64,
+    /// Number of attempts that returned `StmError::Failure` for another reason.
+    pub failures: u64,
+    /// Number of times a thread was parked, waiting for a variable to change.
+    pub waits: u64,
+    /// Number of parked threads woken up by a commit.
+    pub wakeups: u64,
+    /// Number of collections of dead waiters in the wait queues of variables.
+    pub dead_waiter_collections: u64,
+}
+
+impl StmStats {
+    /// Return the current values of the global counters.
+    ///
+    /// The counters are read one after the other, so the snapshot may be slightly inconsistent
+    /// if transactions are running at the same time.
+    pub fn snapshot() -> Self {
+        Self {
+            attempts: STATS.attempts.get(),
+            commits: STATS.commits.get(),
+            commit_failures: STATS.commit_failures.get(),
+            retries: STATS.retries.get(),
+            early_conflicts: STATS.early_conflicts.get(),
+            failures: STATS.failures.get(),
+            waits: STATS.waits.get(),
+            wakeups: STATS.wakeups.get(),
+            dead_waiter_collections: STATS.dead_waiter_collections.get(),
+        }
+    }
+
+    /// Reset the global counters, returning their values before the reset.
+    ///
+    /// Events happening during the reset are either counted in the returned values or in the
+    /// next ones, never lost.
+    pub fn reset() -> Self {
+        Self {
+            attempts: STATS.attempts.take(),
+            commits: STATS.commits.take(),
+            commit_failures: STATS.commit_failures.take(),
+            retries: STATS.retries.take(),
+            early_conflicts: STATS.early_conflicts.take(),
+            failures: STATS.failures.take(),
+            waits: STATS.waits.take(),
+            wakeups: STATS.wakeups.take(),
+            dead_waiter_collections: STATS.dead_waiter_collections.take(),
+        }
+    }
+}
+
+/// Compute the events that happened between two snapshots.
+impl Sub for StmStats {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self {
+            attempts: self.attempts.saturating_sub(rhs.attempts),
+            commits: self.commits.saturating_sub(rhs.commits),
+            commit_failures: self.commit_failures.saturating_sub(rhs.commit_failures),
+            retries: self.retries.saturating_sub(rhs.retries),
+            early_conflicts: self.early_conflicts.saturating_sub(rhs.early_conflicts),
+            failures: self.failures.saturating_sub(rhs.failures),
+            waits: self.waits.saturating_sub(rhs.waits),
+            wakeups: self.wakeups.saturating_sub(rhs.wakeups),
+            dead_waiter_collections: self
+                .dead_waiter_collections
+                .saturating_sub(rhs.dead_waiter_collections),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{atomically, retry, StmError, TVar, Transaction, TransactionControl};
+
+    #[test]
+    fn striped_counter() {
+        let counter = StripedCounter::new();
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| (0..1000).for_each(|_| counter.incr()));
+            }
+        });
+        assert_eq!(counter.get(), 4000);
+        assert_eq!(counter.take(), 4000);
+        assert_eq!(counter.get(), 0);
+    }
+
+    /// Other tests run concurrently, so only lower bounds can be checked.
+    #[test]
+    fn global_events() {
+        let var = TVar::new(0);
+        let before = StmStats::snapshot();
+
+        let mut log = Transaction::default();
+        var.read(&mut log).unwrap();
+        var.write_atomic(1);
+        assert!(!log.commit());
+
+        atomically(|trans| var.modify(trans, |x| x + 1));
+        let aborted: Option<()> =
+            Transaction::with_control(|_| TransactionControl::Abort, |_| retry());
+        assert!(aborted.is_none());
+        let failed: Option<()> =
+            Transaction::with_control(|_| TransactionControl::Abort, |_| Err(StmError::Failure));
+        assert!(failed.is_none());
+
+        let delta = StmStats::snapshot() - before;
+        assert!(delta.attempts >= 2);
+        assert!(delta.commits >= 1);
+        assert!(delta.commit_failures >= 1);
+        assert!(delta.retries >= 1);
+        assert!(delta.failures >= 1);
+    }
+}
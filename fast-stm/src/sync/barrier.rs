@@ -0,0 +1,184 @@
+use std::fmt::{self, Debug};
+
+use crate::{atomically, guard, StmClosureResult, TVar, Transaction};
+
+#[derive(Clone, Copy, Debug)]
+struct BarrierState {
+    /// Number of completed generations.
+    generation: u64,
+    /// Number of parties that arrived in the current generation.
+    arrived: usize,
+}
+
+/// A reusable barrier, stored in a `TVar`.
+///
+/// The barrier lets a fixed number of parties wait for each other. Once the last party of a
+/// generation has arrived, all parties are released, and the barrier can be used again for
+/// the next generation.
+///
+/// Arriving at the barrier must commit before waiting for the other parties, so waiting takes
+/// two transactions: `arrive` returns the generation of the party, which can then be waited for
+/// with `wait_generation`, possibly combined with other conditions. `wait_atomic` runs both.
+///
+/// Cloning a `TBarrier` yields a new handle to the same barrier, like `TVar`.
+///
+/// ```
+/// # use fast_stm::*;
+/// let barrier = TBarrier::new(4);
+///
+/// let handles: Vec<_> = (0..4)
+///     .map(|_| {
+///         let barrier = barrier.clone();
+///         std::thread::spawn(move || barrier.wait_atomic())
+///     })
+///     .collect();
+/// let leaders = handles
+///     .into_iter()
+///     .map(|h| h.join().unwrap())
+///     .filter(|leader| *leader)
+///     .count();
+///
+/// assert_eq!(leaders, 1);
+/// ```
+#[derive(Clone)]
+pub struct TBarrier {
+    parties: usize,
+    state: TVar<BarrierState>,
+}
+
+impl TBarrier {
+    /// Create a barrier for `parties` parties.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `parties` is 0.
+    pub fn new(parties: usize) -> Self {
+        assert!(parties > 0, "a barrier needs at least one party");
+        Self {
+            parties,
+            state: TVar::new(BarrierState {
+                generation: 0,
+                arrived: 0,
+            }),
+        }
+    }
+
+    /// Return the number of parties of the barrier.
+    pub fn parties(&self) -> usize {
+        self.parties
+    }
+
+    /// Arrive at the barrier, and return the generation joined.
+    ///
+    /// If this is the last party of the generation, the generation is completed.
+    pub fn arrive(&self, transaction: &mut Transaction) -> StmClosureResult<u64> {
+        let state = self.state.read(transaction)?;
+        let next = if state.arrived + 1 == self.parties {
+            BarrierState {
+                generation: state.generation + 1,
+                arrived: 0,
+            }
+        } else {
+            BarrierState {
+                generation: state.generation,
+                arrived: state.arrived + 1,
+            }
+        };
+        self.state.write(transaction, next)?;
+        Ok(state.generation)
+    }
+
+    /// Wait until `generation` is completed.
+    ///
+    /// Calls `retry` until the last party of the generation has arrived.
+    pub fn wait_generation(
+        &self,
+        transaction: &mut Transaction,
+        generation: u64,
+    ) -> StmClosureResult<()> {
+        guard(self.generation(transaction)? > generation)
+    }
+
+    /// Return the number of completed generations.
+    pub fn generation(&self, transaction: &mut Transaction) -> StmClosureResult<u64> {
+        Ok(self.state.read(transaction)?.generation)
+    }
+
+    /// Arrive at the barrier and wait for the other parties of the generation.
+    ///
+    /// Return `true` for the last party of the generation, like `std::sync::Barrier`.
+    ///
+    /// <div class="warning">
+    ///
+    /// This method should not be used inside transactions.
+    ///
+    /// </div>
+    pub fn wait_atomic(&self) -> bool {
+        let (generation, leader) = atomically(|transaction| {
+            let generation = self.arrive(transaction)?;
+            Ok((generation, self.generation(transaction)? > generation))
+        });
+        if !leader {
+            atomically(|transaction| self.wait_generation(transaction, generation));
+        }
+        leader
+    }
+}
+
+impl Debug for TBarrier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        let state = self.state.read_atomic();
+        f.debug_struct("TBarrier")
+            .field("parties", &self.parties)
+            .field("generation", &state.generation)
+            .field("arrived", &state.arrived)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn generations() {
+        let barrier = TBarrier::new(2);
+
+        assert_eq!(atomically(|tx| barrier.arrive(tx)), 0);
+        assert_eq!(atomically(|tx| barrier.generation(tx)), 0);
+        assert_eq!(atomically(|tx| barrier.arrive(tx)), 0);
+        assert_eq!(atomically(|tx| barrier.generation(tx)), 1);
+
+        // The first generation is completed, the barrier is reused.
+        atomically(|tx| barrier.wait_generation(tx, 0));
+        assert_eq!(atomically(|tx| barrier.arrive(tx)), 1);
+        assert_eq!(atomically(|tx| barrier.generation(tx)), 1);
+    }
+
+    #[test]
+    fn threaded_phases() {
+        let barrier = TBarrier::new(4);
+        let counter = TVar::new(0);
+
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let barrier = barrier.clone();
+                let counter = counter.clone();
+                std::thread::spawn(move || {
+                    for phase in 0..10 {
+                        atomically(|tx| counter.modify(tx, |x| x + 1));
+                        barrier.wait_atomic();
+                        // Every party of the phase has incremented the counter.
+                        assert!(counter.read_atomic() >= 4 * (phase + 1));
+                        barrier.wait_atomic();
+                    }
+                })
+            })
+            .collect();
+        for h in handles {
+            h.join().unwrap();
+        }
+
+        assert_eq!(atomically(|tx| barrier.generation(tx)), 20);
+    }
+}
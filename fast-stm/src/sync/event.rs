@@ -0,0 +1,123 @@
+use std::fmt::{self, Debug};
+
+use crate::{atomically, guard, StmClosureResult, TVar, Transaction};
+
+/// A manual-reset event, stored in a `TVar`.
+///
+/// Waiting on the event calls `retry` until the event is set. The event stays set, releasing
+/// every waiter, until it is reset.
+///
+/// Cloning a `TEvent` yields a new handle to the same event, like `TVar`.
+///
+/// ```
+/// # use fast_stm::*;
+/// let shutdown = TEvent::new();
+/// let jobs = TDeque::new();
+///
+/// // Take the next job, or wait for one unless the worker is shut down.
+/// let next = |trans: &mut Transaction| {
+///     trans.or(
+///         |trans| jobs.pop_front(trans).map(Some),
+///         |trans| shutdown.wait(trans).map(|()| None),
+///     )
+/// };
+///
+/// atomically(|trans| jobs.push_back(trans, 1));
+/// assert_eq!(atomically(next), Some(1));
+///
+/// atomically(|trans| shutdown.set(trans));
+/// assert_eq!(atomically(next), None);
+/// ```
+#[derive(Clone)]
+pub struct TEvent {
+    set: TVar<bool>,
+}
+
+impl TEvent {
+    /// Create an event that is not set.
+    pub fn new() -> Self {
+        Self {
+            set: TVar::new(false),
+        }
+    }
+
+    /// Set the event, releasing the waiters.
+    pub fn set(&self, transaction: &mut Transaction) -> StmClosureResult<()> {
+        self.set.write(transaction, true)
+    }
+
+    /// Reset the event.
+    pub fn reset(&self, transaction: &mut Transaction) -> StmClosureResult<()> {
+        self.set.write(transaction, false)
+    }
+
+    /// Check if the event is set.
+    pub fn is_set(&self, transaction: &mut Transaction) -> StmClosureResult<bool> {
+        self.set.read(transaction)
+    }
+
+    /// Wait until the event is set.
+    ///
+    /// Calls `retry` while the event is not set.
+    pub fn wait(&self, transaction: &mut Transaction) -> StmClosureResult<()> {
+        guard(self.is_set(transaction)?)
+    }
+
+    /// Wait until the event is set, in its own transaction.
+    ///
+    /// <div class="warning">
+    ///
+    /// This method should not be used inside transactions.
+    ///
+    /// </div>
+    pub fn wait_atomic(&self) {
+        atomically(|transaction| self.wait(transaction));
+    }
+}
+
+impl Default for TEvent {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Debug for TEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        f.debug_struct("TEvent")
+            .field("set", &self.set.read_atomic())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn set_reset() {
+        let event = TEvent::new();
+        assert!(!atomically(|tx| event.is_set(tx)));
+
+        atomically(|tx| event.set(tx));
+        event.wait_atomic();
+        atomically(|tx| event.reset(tx));
+        assert!(!atomically(|tx| event.is_set(tx)));
+    }
+
+    #[test]
+    fn threaded_wait() {
+        let event = TEvent::new();
+        let eventc = event.clone();
+
+        let x = crate::test::async_test(
+            800,
+            move || eventc.wait_atomic(),
+            || {
+                std::thread::sleep(std::time::Duration::from_millis(100));
+                atomically(|tx| event.set(tx));
+            },
+        );
+
+        assert_eq!(x, Some(()));
+    }
+}
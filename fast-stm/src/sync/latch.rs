@@ -0,0 +1,114 @@
+use std::fmt::{self, Debug};
+
+use crate::{atomically, guard, StmClosureResult, TVar, Transaction};
+
+/// A count-down latch, stored in a `TVar`.
+///
+/// The latch starts with a count, decremented by `count_down`. Waiting on the latch calls
+/// `retry` until the count reaches zero. Unlike [`TBarrier`][super::TBarrier], the latch cannot
+/// be reused.
+///
+/// Cloning a `TCountDownLatch` yields a new handle to the same latch, like `TVar`.
+///
+/// ```
+/// # use fast_stm::*;
+/// let ready = TCountDownLatch::new(2);
+///
+/// let handles: Vec<_> = (0..2)
+///     .map(|_| {
+///         let ready = ready.clone();
+///         std::thread::spawn(move || atomically(|trans| ready.count_down(trans)))
+///     })
+///     .collect();
+///
+/// ready.wait_atomic();
+/// # for h in handles { h.join().unwrap(); }
+/// ```
+#[derive(Clone)]
+pub struct TCountDownLatch {
+    count: TVar<usize>,
+}
+
+impl TCountDownLatch {
+    /// Create a latch with the given count.
+    pub fn new(count: usize) -> Self {
+        Self {
+            count: TVar::new(count),
+        }
+    }
+
+    /// Decrement the count of the latch. Does nothing if it has already reached zero.
+    pub fn count_down(&self, transaction: &mut Transaction) -> StmClosureResult<()> {
+        let count = self.count.read(transaction)?;
+        if count > 0 {
+            self.count.write(transaction, count - 1)?;
+        }
+        Ok(())
+    }
+
+    /// Return the current count of the latch.
+    pub fn count(&self, transaction: &mut Transaction) -> StmClosureResult<usize> {
+        self.count.read(transaction)
+    }
+
+    /// Wait until the count reaches zero.
+    ///
+    /// Calls `retry` while the count is not zero.
+    pub fn wait(&self, transaction: &mut Transaction) -> StmClosureResult<()> {
+        guard(self.count(transaction)? == 0)
+    }
+
+    /// Wait until the count reaches zero, in its own transaction.
+    ///
+    /// <div class="warning">
+    ///
+    /// This method should not be used inside transactions.
+    ///
+    /// </div>
+    pub fn wait_atomic(&self) {
+        atomically(|transaction| self.wait(transaction));
+    }
+}
+
+impl Debug for TCountDownLatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        f.debug_struct("TCountDownLatch")
+            .field("count", &self.count.read_atomic())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn count_down() {
+        let latch = TCountDownLatch::new(2);
+
+        atomically(|tx| latch.count_down(tx));
+        assert_eq!(atomically(|tx| latch.count(tx)), 1);
+        atomically(|tx| latch.count_down(tx));
+        atomically(|tx| latch.count_down(tx));
+        assert_eq!(atomically(|tx| latch.count(tx)), 0);
+
+        latch.wait_atomic();
+    }
+
+    #[test]
+    fn threaded_wait() {
+        let latch = TCountDownLatch::new(1);
+        let latchc = latch.clone();
+
+        let x = crate::test::async_test(
+            800,
+            move || latchc.wait_atomic(),
+            || {
+                std::thread::sleep(std::time::Duration::from_millis(100));
+                atomically(|tx| latch.count_down(tx));
+            },
+        );
+
+        assert_eq!(x, Some(()));
+    }
+}
@@ -0,0 +1,36 @@
+//! Synchronization primitives whose state is stored in [`TVar`][crate::TVar]s.
+//!
+//! Acquiring one of these primitives is a transactional operation, so it composes with other
+//! transactional conditions, e.g. using [`Transaction::or`][crate::Transaction::or]. The
+//! guards they return can then be held outside of transactions, like the guards of the
+//! primitives of `std::sync`.
+//!
+//! Waiting on a primitive calls `retry`, so that waiting threads are parked until the state
+//! of the primitive changes.
+
+mod barrier;
+mod event;
+mod latch;
+mod mutex;
+mod once_cell;
+mod rwlock;
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+pub use barrier::TBarrier;
+pub use event::TEvent;
+pub use latch::TCountDownLatch;
+pub use mutex::{TMutex, TMutexGuard};
+pub use once_cell::TOnceCell;
+pub use rwlock::{TRwLock, TRwLockReadGuard, TRwLockWriteGuard};
+
+/// Return a new token identifying a lock acquisition.
+///
+/// A guard is created as soon as a transaction acquires a lock, but the acquisition only takes
+/// effect if the transaction commits. Guards store the token they wrote into the state of the
+/// lock, so that they can check if they actually own the lock before giving access to the data.
+fn new_token() -> u64 {
+    static NEXT_TOKEN: AtomicU64 = AtomicU64::new(1);
+
+    NEXT_TOKEN.fetch_add(1, Ordering::Relaxed)
+}
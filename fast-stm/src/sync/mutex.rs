@@ -0,0 +1,251 @@
+use std::cell::UnsafeCell;
+use std::fmt::{self, Debug};
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+
+use super::new_token;
+use crate::{atomically, retry, StmClosureResult, TVar, Transaction};
+
+/// Token of an unlocked mutex.
+const UNLOCKED: u64 = 0;
+
+/// A mutual exclusion lock whose state is stored in a `TVar`.
+///
+/// Locking the mutex is a transactional operation: `lock` calls `retry` while the mutex is held,
+/// and can be combined with other operations, for example to lock whichever of two mutexes is
+/// free first. Once the transaction has committed, the returned guard gives access to the data
+/// until it is dropped, which unlocks the mutex using `atomically`.
+///
+/// A guard created by a transaction that does not commit does not own the mutex. Accessing the
+/// data through it panics, and dropping it has no effect.
+///
+/// <div class="warning">
+///
+/// Guards owning the mutex must not be dropped inside a transaction.
+///
+/// </div>
+///
+/// ```
+/// # use fast_stm::*;
+/// let a = TMutex::new(Vec::new());
+/// let b = TMutex::new(Vec::new());
+///
+/// let mut guard = atomically(|trans| trans.or(|trans| a.lock(trans), |trans| b.lock(trans)));
+/// guard.push(42);
+/// drop(guard);
+///
+/// assert_eq!(a.into_inner(), [42]);
+/// ```
+pub struct TMutex<T> {
+    /// Token of the guard owning the mutex, or `UNLOCKED`.
+    owner: TVar<u64>,
+    data: UnsafeCell<T>,
+}
+
+// The data is only accessed through the guard owning the mutex, like `std::sync::Mutex`.
+unsafe impl<T: Send> Send for TMutex<T> {}
+unsafe impl<T: Send> Sync for TMutex<T> {}
+
+impl<T> TMutex<T> {
+    /// Create a new unlocked mutex holding `value`.
+    pub fn new(value: T) -> Self {
+        Self {
+            owner: TVar::new(UNLOCKED),
+            data: UnsafeCell::new(value),
+        }
+    }
+
+    /// Lock the mutex.
+    ///
+    /// Calls `retry` if the mutex is locked, blocking the transaction until it is unlocked.
+    pub fn lock(&self, transaction: &mut Transaction) -> StmClosureResult<TMutexGuard<'_, T>> {
+        match self.try_lock(transaction)? {
+            Some(guard) => Ok(guard),
+            None => retry(),
+        }
+    }
+
+    /// Lock the mutex, or return `None` if it is locked.
+    pub fn try_lock(
+        &self,
+        transaction: &mut Transaction,
+    ) -> StmClosureResult<Option<TMutexGuard<'_, T>>> {
+        if self.owner.read(transaction)? != UNLOCKED {
+            return Ok(None);
+        }
+        let token = new_token();
+        self.owner.write(transaction, token)?;
+        Ok(Some(TMutexGuard {
+            mutex: self,
+            token,
+            _marker: PhantomData,
+        }))
+    }
+
+    /// Lock the mutex in its own transaction, blocking until it is unlocked.
+    ///
+    /// <div class="warning">
+    ///
+    /// This method should not be used inside transactions.
+    ///
+    /// </div>
+    pub fn lock_atomic(&self) -> TMutexGuard<'_, T> {
+        atomically(|transaction| self.lock(transaction))
+    }
+
+    /// Check if the mutex is locked.
+    pub fn is_locked(&self, transaction: &mut Transaction) -> StmClosureResult<bool> {
+        Ok(self.owner.read(transaction)? != UNLOCKED)
+    }
+
+    /// Return a mutable reference to the data.
+    ///
+    /// No locking is needed, since the mutex is borrowed mutably.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    /// Consume the mutex, returning the data.
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: Default> Default for TMutex<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T> Debug for TMutex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        f.debug_struct("TMutex")
+            .field("locked", &(self.owner.read_atomic() != UNLOCKED))
+            .finish_non_exhaustive()
+    }
+}
+
+/// A guard of a [`TMutex`], unlocking it when dropped.
+pub struct TMutexGuard<'a, T> {
+    mutex: &'a TMutex<T>,
+    token: u64,
+    /// Make the guard `Sync` only if `T` is.
+    _marker: PhantomData<&'a mut T>,
+}
+
+impl<T> TMutexGuard<'_, T> {
+    /// Check if the transaction that created the guard has committed.
+    fn owns_lock(&self) -> bool {
+        self.mutex.owner.read_atomic() == self.token
+    }
+
+    fn check(&self) {
+        assert!(
+            self.owns_lock(),
+            "STM: guard of a TMutex created by a transaction that did not commit"
+        );
+    }
+}
+
+impl<T> Deref for TMutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.check();
+        // SAFETY: the guard owns the mutex, no other guard can access the data.
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<T> DerefMut for TMutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.check();
+        // SAFETY: the guard owns the mutex, no other guard can access the data.
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+impl<T> Drop for TMutexGuard<'_, T> {
+    fn drop(&mut self) {
+        if self.owns_lock() {
+            atomically(|transaction| self.mutex.owner.write(transaction, UNLOCKED));
+        }
+    }
+}
+
+impl<T: Debug> Debug for TMutexGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        Debug::fmt(&**self, f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn lock_unlock() {
+        let mutex = TMutex::new(0);
+
+        let mut guard = mutex.lock_atomic();
+        *guard += 1;
+        assert!(atomically(|tx| mutex.is_locked(tx)));
+        assert!(atomically(|tx| mutex.try_lock(tx)).is_none());
+        drop(guard);
+
+        assert!(!atomically(|tx| mutex.is_locked(tx)));
+        assert_eq!(*mutex.lock_atomic(), 1);
+    }
+
+    /// `or` locks the first free mutex.
+    #[test]
+    fn lock_either() {
+        let a = TMutex::new("a");
+        let b = TMutex::new("b");
+
+        let lock_any = || atomically(|tx| tx.or(|tx| a.lock(tx), |tx| b.lock(tx)));
+        let first = lock_any();
+        let second = lock_any();
+        assert_eq!((*first, *second), ("a", "b"));
+
+        drop(first);
+        assert_eq!(*lock_any(), "a");
+    }
+
+    /// A guard created by a transaction that did not commit does not own the mutex.
+    #[test]
+    #[should_panic]
+    fn uncommitted_guard() {
+        let mutex = TMutex::new(0);
+
+        let mut tx = Transaction::default();
+        let guard = mutex.lock(&mut tx).unwrap();
+        drop(tx);
+
+        let _ = *guard;
+    }
+
+    #[test]
+    fn threaded_lock() {
+        let mutex = std::sync::Arc::new(TMutex::new(0));
+
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let mutex = mutex.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..100 {
+                        let mut guard = mutex.lock_atomic();
+                        let value = *guard;
+                        std::thread::yield_now();
+                        *guard = value + 1;
+                    }
+                })
+            })
+            .collect();
+        for h in handles {
+            h.join().unwrap();
+        }
+
+        assert_eq!(*mutex.lock_atomic(), 400);
+    }
+}
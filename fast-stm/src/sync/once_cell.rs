@@ -0,0 +1,165 @@
+use std::any::Any;
+use std::fmt::{self, Debug};
+use std::sync::Arc;
+
+use crate::{
+    abort, unwrap_or_retry, StmClosureResult, TVar, Transaction, TransactionClosureResult,
+};
+
+/// A cell that can be written only once, stored in a `TVar`.
+///
+/// Since the cell is set by a transaction, its initializer can read other `TVar`s, and is only
+/// committed if they are consistent. Transactions waiting for the value with `get` are parked
+/// until the cell is set.
+///
+/// The value is stored in an `Arc`, so that reading it does not clone it.
+///
+/// Cloning a `TOnceCell` yields a new handle to the same cell, like `TVar`.
+///
+/// ```
+/// # use fast_stm::*;
+/// let base = TVar::new(40);
+/// let config = TOnceCell::new();
+///
+/// let value = atomically(|trans| config.get_or_init_with(trans, |trans| Ok(base.read(trans)? + 2)));
+/// assert_eq!(*value, 42);
+///
+/// // The cell is already set.
+/// assert_eq!(atomically_with_err(|trans| config.set(trans, 0)), Err(0));
+/// ```
+pub struct TOnceCell<T> {
+    value: TVar<Option<Arc<T>>>,
+}
+
+impl<T> TOnceCell<T>
+where
+    T: Any + Send + Sync,
+{
+    /// Create an empty cell.
+    pub fn new() -> Self {
+        Self {
+            value: TVar::new(None),
+        }
+    }
+
+    /// Return the value of the cell.
+    ///
+    /// Calls `retry` while the cell is empty, blocking the transaction until it is set.
+    pub fn get(&self, transaction: &mut Transaction) -> StmClosureResult<Arc<T>> {
+        unwrap_or_retry(self.try_get(transaction)?)
+    }
+
+    /// Return the value of the cell, or `None` if it is empty.
+    pub fn try_get(&self, transaction: &mut Transaction) -> StmClosureResult<Option<Arc<T>>> {
+        self.value.read(transaction)
+    }
+
+    /// Set the value of the cell.
+    ///
+    /// Aborts the transaction with `value` if the cell is already set.
+    pub fn set(&self, transaction: &mut Transaction, value: T) -> TransactionClosureResult<(), T> {
+        if self.try_get(transaction)?.is_some() {
+            return abort(value);
+        }
+        self.value.write(transaction, Some(Arc::new(value)))?;
+        Ok(())
+    }
+
+    /// Return the value of the cell, setting it with the transactional closure `f` if it is
+    /// empty.
+    pub fn get_or_init_with<F>(
+        &self,
+        transaction: &mut Transaction,
+        f: F,
+    ) -> StmClosureResult<Arc<T>>
+    where
+        F: FnOnce(&mut Transaction) -> StmClosureResult<T>,
+    {
+        if let Some(value) = self.try_get(transaction)? {
+            return Ok(value);
+        }
+        let value = Arc::new(f(transaction)?);
+        self.value.write(transaction, Some(value.clone()))?;
+        Ok(value)
+    }
+}
+
+impl<T> Default for TOnceCell<T>
+where
+    T: Any + Send + Sync,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Clone for TOnceCell<T> {
+    fn clone(&self) -> Self {
+        Self {
+            value: self.value.clone(),
+        }
+    }
+}
+
+impl<T> Debug for TOnceCell<T>
+where
+    T: Any + Send + Sync + Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        f.debug_tuple("TOnceCell")
+            .field(&self.value.read_atomic())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{atomically, atomically_with_err};
+
+    #[test]
+    fn set_once() {
+        let cell = TOnceCell::new();
+        assert_eq!(atomically(|tx| cell.try_get(tx)), None);
+
+        assert_eq!(atomically_with_err(|tx| cell.set(tx, 1)), Ok(()));
+        assert_eq!(atomically_with_err(|tx| cell.set(tx, 2)), Err(2));
+        assert_eq!(*atomically(|tx| cell.get(tx)), 1);
+    }
+
+    /// The initializer only runs if the cell is empty.
+    #[test]
+    fn init_once() {
+        let cell = TOnceCell::new();
+        let runs = TVar::new(0);
+
+        for _ in 0..3 {
+            let value = atomically(|tx| {
+                cell.get_or_init_with(tx, |tx| {
+                    runs.modify(tx, |x| x + 1)?;
+                    Ok("init")
+                })
+            });
+            assert_eq!(*value, "init");
+        }
+        assert_eq!(runs.read_atomic(), 1);
+    }
+
+    /// `get` blocks until the cell is set.
+    #[test]
+    fn threaded_get() {
+        let cell = TOnceCell::new();
+        let cellc = cell.clone();
+
+        let x = crate::test::async_test(
+            800,
+            move || *atomically(|tx| cellc.get(tx)),
+            || {
+                std::thread::sleep(std::time::Duration::from_millis(100));
+                atomically_with_err(|tx| cell.set(tx, 42)).unwrap();
+            },
+        );
+
+        assert_eq!(x, Some(42));
+    }
+}
@@ -0,0 +1,332 @@
+use std::cell::UnsafeCell;
+use std::fmt::{self, Debug};
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+
+use super::new_token;
+use crate::{atomically, retry, StmClosureResult, TVar, Transaction};
+
+/// A reader-writer lock whose state is stored in `TVar`s.
+///
+/// Like [`TMutex`][super::TMutex], acquiring the lock is a transactional operation that calls
+/// `retry` while the lock is not available, and returns a guard that releases the lock using
+/// `atomically` when dropped. Any number of readers can hold the lock at the same time, while
+/// a writer holds it exclusively. Writers are not given priority over readers.
+///
+/// Readers only read the writer's state and update the number of readers with
+/// [`Transaction::commute`], so concurrent readers do not conflict with each other.
+///
+/// A guard created by a transaction that does not commit does not hold the lock. Accessing the
+/// data through it panics, and dropping it has no effect.
+///
+/// <div class="warning">
+///
+/// Guards holding the lock must not be dropped inside a transaction.
+///
+/// </div>
+///
+/// ```
+/// # use fast_stm::*;
+/// let config = TRwLock::new(String::from("v1"));
+///
+/// let reader = config.read_atomic();
+/// assert!(atomically(|trans| config.try_write(trans)).is_none());
+/// drop(reader);
+///
+/// config.write_atomic().push_str(".1");
+/// assert_eq!(*config.read_atomic(), "v1.1");
+/// ```
+pub struct TRwLock<T> {
+    /// Token of the guard owning the lock exclusively, if any.
+    writer: TVar<Option<u64>>,
+    /// Number of guards sharing the lock.
+    readers: TVar<usize>,
+    data: UnsafeCell<T>,
+}
+
+// The data is only accessed through the guards holding the lock, like `std::sync::RwLock`.
+unsafe impl<T: Send> Send for TRwLock<T> {}
+unsafe impl<T: Send + Sync> Sync for TRwLock<T> {}
+
+impl<T> TRwLock<T> {
+    /// Create a new unlocked lock holding `value`.
+    pub fn new(value: T) -> Self {
+        Self {
+            writer: TVar::new(None),
+            readers: TVar::new(0),
+            data: UnsafeCell::new(value),
+        }
+    }
+
+    /// Lock with shared read access.
+    ///
+    /// Calls `retry` if the lock is held by a writer, blocking the transaction until it is
+    /// released.
+    pub fn read(&self, transaction: &mut Transaction) -> StmClosureResult<TRwLockReadGuard<'_, T>> {
+        match self.try_read(transaction)? {
+            Some(guard) => Ok(guard),
+            None => retry(),
+        }
+    }
+
+    /// Lock with shared read access, or return `None` if the lock is held by a writer.
+    pub fn try_read(
+        &self,
+        transaction: &mut Transaction,
+    ) -> StmClosureResult<Option<TRwLockReadGuard<'_, T>>> {
+        if self.writer.read(transaction)?.is_some() {
+            return Ok(None);
+        }
+        transaction.commute(&self.readers, |readers| readers + 1)?;
+        // The guard gets its own variable, so that readers do not write the same token.
+        let held = TVar::new(false);
+        held.write(transaction, true)?;
+        Ok(Some(TRwLockReadGuard {
+            lock: self,
+            held,
+            _marker: PhantomData,
+        }))
+    }
+
+    /// Lock with exclusive write access.
+    ///
+    /// Calls `retry` if the lock is held, blocking the transaction until it is released.
+    pub fn write(
+        &self,
+        transaction: &mut Transaction,
+    ) -> StmClosureResult<TRwLockWriteGuard<'_, T>> {
+        match self.try_write(transaction)? {
+            Some(guard) => Ok(guard),
+            None => retry(),
+        }
+    }
+
+    /// Lock with exclusive write access, or return `None` if the lock is held.
+    pub fn try_write(
+        &self,
+        transaction: &mut Transaction,
+    ) -> StmClosureResult<Option<TRwLockWriteGuard<'_, T>>> {
+        if self.writer.read(transaction)?.is_some() || self.readers.read(transaction)? > 0 {
+            return Ok(None);
+        }
+        let token = new_token();
+        self.writer.write(transaction, Some(token))?;
+        Ok(Some(TRwLockWriteGuard {
+            lock: self,
+            token,
+            _marker: PhantomData,
+        }))
+    }
+
+    /// Lock with shared read access in its own transaction, blocking until it is available.
+    ///
+    /// <div class="warning">
+    ///
+    /// This method should not be used inside transactions.
+    ///
+    /// </div>
+    pub fn read_atomic(&self) -> TRwLockReadGuard<'_, T> {
+        atomically(|transaction| self.read(transaction))
+    }
+
+    /// Lock with exclusive write access in its own transaction, blocking until it is available.
+    ///
+    /// <div class="warning">
+    ///
+    /// This method should not be used inside transactions.
+    ///
+    /// </div>
+    pub fn write_atomic(&self) -> TRwLockWriteGuard<'_, T> {
+        atomically(|transaction| self.write(transaction))
+    }
+
+    /// Return a mutable reference to the data.
+    ///
+    /// No locking is needed, since the lock is borrowed mutably.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    /// Consume the lock, returning the data.
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: Default> Default for TRwLock<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T> Debug for TRwLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        f.debug_struct("TRwLock")
+            .field("readers", &self.readers.read_atomic())
+            .field("writer", &self.writer.read_atomic().is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+/// A guard of a [`TRwLock`] with shared read access, releasing it when dropped.
+pub struct TRwLockReadGuard<'a, T> {
+    lock: &'a TRwLock<T>,
+    /// Set by the transaction creating the guard, so it is only true if it committed.
+    held: TVar<bool>,
+    /// Make the guard `Send` and `Sync` only if `T` is `Sync`.
+    _marker: PhantomData<&'a T>,
+}
+
+impl<T> Deref for TRwLockReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        assert!(
+            self.held.read_atomic(),
+            "STM: guard of a TRwLock created by a transaction that did not commit"
+        );
+        // SAFETY: the guard holds the lock, no writer can access the data.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T> Drop for TRwLockReadGuard<'_, T> {
+    fn drop(&mut self) {
+        if self.held.read_atomic() {
+            atomically(|transaction| {
+                transaction.commute(&self.lock.readers, |readers| readers - 1)
+            });
+        }
+    }
+}
+
+impl<T: Debug> Debug for TRwLockReadGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        Debug::fmt(&**self, f)
+    }
+}
+
+/// A guard of a [`TRwLock`] with exclusive write access, releasing it when dropped.
+pub struct TRwLockWriteGuard<'a, T> {
+    lock: &'a TRwLock<T>,
+    token: u64,
+    /// Make the guard `Sync` only if `T` is.
+    _marker: PhantomData<&'a mut T>,
+}
+
+impl<T> TRwLockWriteGuard<'_, T> {
+    fn check(&self) {
+        assert!(
+            self.lock.writer.read_atomic() == Some(self.token),
+            "STM: guard of a TRwLock created by a transaction that did not commit"
+        );
+    }
+}
+
+impl<T> Deref for TRwLockWriteGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.check();
+        // SAFETY: the guard holds the lock exclusively.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T> DerefMut for TRwLockWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.check();
+        // SAFETY: the guard holds the lock exclusively.
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<T> Drop for TRwLockWriteGuard<'_, T> {
+    fn drop(&mut self) {
+        if self.lock.writer.read_atomic() == Some(self.token) {
+            atomically(|transaction| self.lock.writer.write(transaction, None));
+        }
+    }
+}
+
+impl<T: Debug> Debug for TRwLockWriteGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        Debug::fmt(&**self, f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn shared_readers() {
+        let lock = TRwLock::new(1);
+
+        let r1 = lock.read_atomic();
+        let r2 = lock.read_atomic();
+        assert_eq!(*r1 + *r2, 2);
+        assert!(atomically(|tx| lock.try_write(tx)).is_none());
+
+        drop(r1);
+        assert!(atomically(|tx| lock.try_write(tx)).is_none());
+        drop(r2);
+
+        let mut w = lock.write_atomic();
+        *w = 5;
+        assert!(atomically(|tx| lock.try_read(tx)).is_none());
+        drop(w);
+
+        assert_eq!(*lock.read_atomic(), 5);
+    }
+
+    /// A guard created by a transaction that did not commit does not hold the lock.
+    #[test]
+    #[should_panic]
+    fn uncommitted_guard() {
+        let lock = TRwLock::new(0);
+
+        let mut tx = Transaction::default();
+        let guard = lock.write(&mut tx).unwrap();
+        drop(tx);
+
+        let _ = *guard;
+    }
+
+    /// Readers share the lock without conflicting with each other.
+    #[test]
+    fn readers_no_conflict() {
+        let lock = TRwLock::new(0);
+
+        let mut tx1 = Transaction::default();
+        let mut tx2 = Transaction::default();
+        let r1 = lock.read(&mut tx1).unwrap();
+        let r2 = lock.read(&mut tx2).unwrap();
+        assert!(tx1.commit());
+        assert!(tx2.commit());
+        assert_eq!(lock.readers.read_atomic(), 2);
+
+        drop(r1);
+        assert!(atomically(|tx| lock.try_write(tx)).is_none());
+        drop(r2);
+        assert!(atomically(|tx| lock.try_write(tx)).is_some());
+    }
+
+    /// A writer waits for the readers to release the lock.
+    #[test]
+    fn threaded_write() {
+        let lock = std::sync::Arc::new(TRwLock::new(0));
+        let lockc = lock.clone();
+        let reader = lock.read_atomic();
+
+        let handle = std::thread::spawn(move || {
+            *lockc.write_atomic() = 42;
+        });
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        assert_eq!(*reader, 0);
+        drop(reader);
+
+        handle.join().unwrap();
+        assert_eq!(*lock.read_atomic(), 42);
+    }
+}
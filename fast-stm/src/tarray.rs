@@ -0,0 +1,372 @@
+use parking_lot::RwLock;
+use std::any::Any;
+use std::fmt::{self, Debug};
+use std::sync::Arc;
+
+use super::result::StmClosureResult;
+#[cfg(feature = "wait-on-retry")]
+use super::tvar::WaitQueue;
+use super::tvar::{Cell, Storage, VarRef};
+use super::Transaction;
+
+/// `ArrayControlBlock` contains the data of all the slots of a `TArray`.
+///
+/// Each slot only stores its value; the other data is shared by the whole array.
+pub struct ArrayControlBlock<T> {
+    /// Threads waiting for a change of any slot of the array.
+    #[cfg(feature = "wait-on-retry")]
+    pub waiters: WaitQueue,
+
+    /// The inner values of the slots.
+    ///
+    /// Each value is handled like the value of a `VarControlBlock`.
+    pub slots: Box<[RwLock<Arc<T>>]>,
+}
+
+impl<T> Storage for ArrayControlBlock<T>
+where
+    T: Any + Sync + Send,
+{
+    fn cell(&self, idx: usize) -> &dyn Cell {
+        &self.slots[idx]
+    }
+
+    #[cfg(diagnostics)]
+    fn name(&self, _idx: usize) -> Option<&'static str> {
+        None
+    }
+
+    #[cfg(feature = "wait-on-retry")]
+    fn waiters(&self) -> &WaitQueue {
+        &self.waiters
+    }
+}
+
+/// A fixed-size array of variables that can be used in a STM-Block.
+///
+/// A `TArray<T>` behaves like a `Vec<TVar<T>>`: each slot is tracked separately by transactions,
+/// so that transactions accessing different slots do not conflict. Unlike a `Vec` of `TVar`s,
+/// the control data of all slots is allocated in a single block.
+///
+/// Threads waiting on `retry` are tracked at the array level: writing to any slot wakes up all
+/// the transactions waiting on a slot of the array.
+///
+/// ```
+/// # use fast_stm::*;
+/// let array = TArray::new([0; 4]);
+///
+/// atomically(|trans| {
+///     array.write(trans, 1, 42)?;
+///     array.modify(trans, 2, |x| x + 1)
+/// });
+///
+/// assert_eq!(array.read_atomic(1), 42);
+/// assert_eq!(array.read_atomic(2), 1);
+/// ```
+pub struct TArray<T> {
+    /// The control block holding all the slots.
+    control_block: Arc<ArrayControlBlock<T>>,
+}
+
+impl<T> TArray<T>
+where
+    T: Any + Sync + Send + Clone,
+{
+    /// Create a new `TArray` holding `values`.
+    pub fn new<I>(values: I) -> TArray<T>
+    where
+        I: IntoIterator<Item = T>,
+    {
+        let slots = values
+            .into_iter()
+            .map(|val| RwLock::new(Arc::new(val)))
+            .collect();
+        TArray {
+            control_block: Arc::new(ArrayControlBlock {
+                #[cfg(feature = "wait-on-retry")]
+                waiters: WaitQueue::default(),
+                slots,
+            }),
+        }
+    }
+
+    /// Return the number of slots of the array.
+    pub fn len(&self) -> usize {
+        self.control_block.slots.len()
+    }
+
+    /// Check if the array has no slots.
+    pub fn is_empty(&self) -> bool {
+        self.control_block.slots.is_empty()
+    }
+
+    /// Return the number of transactions parked on `retry` until a slot of the array changes.
+    ///
+    /// A transaction waiting for several slots is counted once.
+    #[cfg(feature = "wait-on-retry")]
+    pub fn waiter_count(&self) -> usize {
+        self.control_block.waiters.waiting()
+    }
+
+    /// Return the number of stale entries in the wait queue of the array.
+    ///
+    /// A transaction that waited for several slots leaves one entry per slot. See
+    /// `TVar::dead_waiter_count`.
+    #[cfg(feature = "wait-on-retry")]
+    pub fn dead_waiter_count(&self) -> usize {
+        self.control_block.waiters.dead()
+    }
+
+    /// `read_atomic` reads the value of slot `idx` atomically, without starting a transaction.
+    ///
+    /// <div class="warning">
+    ///
+    /// This method should not be used inside transactions.
+    ///
+    /// </div>
+    ///
+    /// # Panics
+    ///
+    /// Panics if `idx` is out of bounds.
+    pub fn read_atomic(&self, idx: usize) -> T {
+        T::clone(&self.cell(idx).read())
+    }
+
+    /// `write_atomic` writes the value of slot `idx` atomically, without starting a transaction.
+    ///
+    /// <div class="warning">
+    ///
+    /// This method should not be used inside transactions.
+    ///
+    /// </div>
+    ///
+    /// # Panics
+    ///
+    /// Panics if `idx` is out of bounds.
+    pub fn write_atomic(&self, idx: usize, value: T) {
+        *self.cell(idx).write() = Arc::new(value);
+    }
+
+    /// Read the value of slot `idx`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `idx` is out of bounds.
+    pub fn read(&self, transaction: &mut Transaction, idx: usize) -> StmClosureResult<T> {
+        transaction
+            .read_value(self.slot(idx), self.cell(idx))
+            .map(|value| T::clone(&value))
+    }
+
+    /// Write the value of slot `idx`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `idx` is out of bounds.
+    pub fn write(
+        &self,
+        transaction: &mut Transaction,
+        idx: usize,
+        value: T,
+    ) -> StmClosureResult<()> {
+        transaction.write_value(self.slot(idx), Arc::new(value));
+        Ok(())
+    }
+
+    /// Modify the value of slot `idx` with the function f.
+    ///
+    /// Prefer this method over calling `read` then `write` for performance.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `idx` is out of bounds.
+    pub fn modify<F>(&self, transaction: &mut Transaction, idx: usize, f: F) -> StmClosureResult<()>
+    where
+        F: FnOnce(T) -> T,
+    {
+        transaction
+            .update_value(self.slot(idx), self.cell(idx), |value| {
+                Arc::new(f(T::clone(value)))
+            })
+            .map(|_| ())
+    }
+
+    /// Update the value of slot `idx` with the commutative function f.
+    ///
+    /// The update is applied when committing, and does not conflict with other transactions.
+    /// See [`Transaction::commute`] for more details.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `idx` is out of bounds.
+    pub fn commute<F>(
+        &self,
+        transaction: &mut Transaction,
+        idx: usize,
+        f: F,
+    ) -> StmClosureResult<()>
+    where
+        F: Fn(T) -> T + Send + Sync + 'static,
+    {
+        transaction.commute_value(self.slot(idx), move |value: &T| f(value.clone()));
+        Ok(())
+    }
+
+    /// Replace the value of slot `idx` with a new one, returning the old one.
+    ///
+    /// Prefer this method over calling `read` then `write` for performance.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `idx` is out of bounds.
+    pub fn exchange(
+        &self,
+        transaction: &mut Transaction,
+        idx: usize,
+        value: T,
+    ) -> StmClosureResult<T> {
+        transaction
+            .update_value(self.slot(idx), self.cell(idx), |_| Arc::new(value))
+            .map(|value| T::clone(&value))
+    }
+
+    /// Read the values of all slots.
+    pub fn to_vec(&self, transaction: &mut Transaction) -> StmClosureResult<Vec<T>> {
+        (0..self.len())
+            .map(|idx| self.read(transaction, idx))
+            .collect()
+    }
+
+    /// Check if two `TArray`s refer to the same position.
+    pub fn ref_eq(this: &TArray<T>, other: &TArray<T>) -> bool {
+        Arc::ptr_eq(&this.control_block, &other.control_block)
+    }
+
+    /// Create a handle to a slot, used as a key in transaction logs.
+    pub(crate) fn slot(&self, idx: usize) -> VarRef {
+        let len = self.len();
+        assert!(
+            idx < len,
+            "index out of bounds: the len is {len} but the index is {idx}"
+        );
+        VarRef::new(self.control_block.clone(), idx)
+    }
+
+    /// Access the storage of slot `idx`.
+    fn cell(&self, idx: usize) -> &RwLock<Arc<T>> {
+        &self.control_block.slots[idx]
+    }
+}
+
+impl<T> Clone for TArray<T> {
+    fn clone(&self) -> Self {
+        TArray {
+            control_block: self.control_block.clone(),
+        }
+    }
+}
+
+impl<T> FromIterator<T> for TArray<T>
+where
+    T: Any + Sync + Send + Clone,
+{
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        TArray::new(iter)
+    }
+}
+
+/// Debug output the array.
+impl<T> Debug for TArray<T>
+where
+    T: Any + Sync + Send + Clone,
+    T: Debug,
+{
+    #[inline(never)]
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        f.debug_list()
+            .entries((0..self.len()).map(|idx| self.read_atomic(idx)))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{atomically, retry};
+
+    #[test]
+    fn read_write_slots() {
+        let array: TArray<_> = (0..4).collect();
+        assert_eq!(array.len(), 4);
+
+        let old = atomically(|tx| {
+            array.write(tx, 0, 10)?;
+            array.modify(tx, 1, |x| x * 10)?;
+            array.exchange(tx, 2, 30)
+        });
+
+        assert_eq!(old, 2);
+        assert_eq!(atomically(|tx| array.to_vec(tx)), [10, 10, 30, 3]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn out_of_bounds() {
+        let array = TArray::new([0; 4]);
+        array.read_atomic(4);
+    }
+
+    /// Transactions accessing different slots should not conflict.
+    #[test]
+    fn per_slot_conflicts() {
+        let array = TArray::new([0; 2]);
+
+        let mut tx1 = Transaction::default();
+        let mut tx2 = Transaction::default();
+        array.modify(&mut tx1, 0, |x| x + 1).unwrap();
+        array.modify(&mut tx2, 1, |x| x + 1).unwrap();
+        assert!(tx2.commit());
+        assert!(tx1.commit());
+
+        let mut tx1 = Transaction::default();
+        let mut tx2 = Transaction::default();
+        array.modify(&mut tx1, 0, |x| x + 1).unwrap();
+        array.modify(&mut tx2, 0, |x| x + 1).unwrap();
+        assert!(tx2.commit());
+        assert!(!tx1.commit());
+
+        assert_eq!(array.read_atomic(0), 2);
+        assert_eq!(array.read_atomic(1), 1);
+    }
+
+    /// A transaction blocked on slots is woken up by a write to one of them.
+    #[test]
+    fn threaded_wakeup() {
+        let array = TArray::new([0; 8]);
+        let arrayc = array.clone();
+
+        let x = crate::test::async_test(
+            800,
+            move || {
+                atomically(|tx| {
+                    arrayc.read(tx, 6)?;
+                    match arrayc.read(tx, 5)? {
+                        0 => retry(),
+                        x => Ok(x),
+                    }
+                })
+            },
+            || {
+                std::thread::sleep(std::time::Duration::from_millis(100));
+                // the transaction waits for two slots, but is counted once
+                #[cfg(feature = "wait-on-retry")]
+                assert_eq!(array.waiter_count(), 1);
+                atomically(|tx| array.write(tx, 5, 42));
+            },
+        );
+
+        assert_eq!(x, Some(42));
+        #[cfg(feature = "wait-on-retry")]
+        assert_eq!(array.waiter_count() + array.dead_waiter_count(), 0);
+    }
+}
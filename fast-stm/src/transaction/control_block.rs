@@ -1,6 +1,8 @@
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::thread::{self, Thread};
 use std::time::Duration;
+#[cfg(feature = "watchdog")]
+use std::time::Instant;
 
 #[cfg(test)]
 use super::super::test::{terminates, terminates_async};
@@ -36,7 +38,7 @@ impl ControlBlock {
         ControlBlock {
             thread: thread::current(),
             blocked: AtomicBool::new(true),
-            max_parked_time: Duration::from_millis(1000),
+            max_parked_time: Duration::from_secs(1),
         }
     }
 
@@ -66,6 +68,22 @@ impl ControlBlock {
             thread::park_timeout(self.max_parked_time);
         }
     }
+
+    /// Block until one variable has changed, or until `timeout` has elapsed.
+    ///
+    /// Return `true` if a variable has changed.
+    #[cfg(feature = "watchdog")]
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        while self.blocked.load(Ordering::SeqCst) {
+            let now = Instant::now();
+            if now >= deadline {
+                return false;
+            }
+            thread::park_timeout((deadline - now).min(self.max_parked_time));
+        }
+        true
+    }
 }
 
 // TESTS
@@ -109,6 +127,16 @@ mod test {
         assert!(terminates(50, move || ctrl.wait()));
     }
 
+    /// `wait_timeout` returns when the timeout elapses, or right away after a change.
+    #[cfg(feature = "watchdog")]
+    #[test]
+    fn wait_timeout() {
+        let ctrl = ControlBlock::new();
+        assert!(!ctrl.wait_timeout(Duration::from_millis(20)));
+        ctrl.set_changed();
+        assert!(ctrl.wait_timeout(Duration::from_secs(10)));
+    }
+
     /// Perform a wakeup from another thread.
     #[test]
     fn wait_threaded_wakeup() {
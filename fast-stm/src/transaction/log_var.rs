@@ -3,6 +3,35 @@ use std::sync::Arc;
 
 pub type ArcAny = Arc<dyn Any + Send + Sync>;
 
+/// Cast a value taken from a variable or a transaction log back to the type of the variable.
+///
+/// The values of a variable are only created by the typed methods of `TVar` and `TArray`, and
+/// the control blocks store them with their type, so a value logged for a variable always has
+/// the type of the variable. This makes checking the `TypeId` unnecessary.
+pub fn downcast_arc<T>(value: ArcAny) -> Arc<T>
+where
+    T: Any + Send + Sync,
+{
+    debug_assert!(value.is::<T>(), "TVar has wrong type");
+    // SAFETY: the value is a `T`, see above, and the pointer comes from `Arc::into_raw`.
+    unsafe { Arc::from_raw(Arc::into_raw(value).cast::<T>()) }
+}
+
+/// Borrow a value taken from a variable or a transaction log with the type of the variable.
+///
+/// See `downcast_arc`.
+pub fn downcast_ref<T>(value: &ArcAny) -> &T
+where
+    T: Any + Send + Sync,
+{
+    debug_assert!(value.is::<T>(), "TVar has wrong type");
+    // SAFETY: the value is a `T`, see `downcast_arc`.
+    unsafe { &*Arc::as_ptr(value).cast::<T>() }
+}
+
+/// Function updating the value of a var, applied when committing.
+pub type Commutation = Arc<dyn Fn(&ArcAny) -> ArcAny + Send + Sync>;
+
 /// `LogVar` is used by `Log` to track which `Var` was either read or written or both.
 /// Depending on the type, STM has to write, ensure consistency or block on this value.
 #[derive(Clone)]
@@ -37,7 +66,14 @@ pub enum LogVar {
     /// so that the threat wakes up when the first path
     /// has been unlocked.
     ReadObsoleteWrite(ArcAny, ArcAny), // Here would be WriteObsolete, but the write onlies can be discarded immediately
-                                       // and don't need a representation in the log.
+    // and don't need a representation in the log.
+    /// Var has been updated using commutative functions, but not read.
+    ///
+    /// The functions are applied in order to the current value of the var when committing,
+    /// so there is no need to check for consistency.
+    ///
+    /// It needs to be turned into a `ReadWrite` before reading the var.
+    Commute(Vec<Commutation>),
 }
 
 impl LogVar {
@@ -65,6 +101,8 @@ impl LogVar {
                 val = v.clone();
                 this = Self::Read(v.clone());
             }
+
+            Self::Commute(_) => unreachable!("commutations are resolved before reading"),
         }
         *self = this;
         val
@@ -75,7 +113,7 @@ impl LogVar {
         let this = self.clone();
 
         *self = match this {
-            Self::Write(_) => Self::Write(w),
+            Self::Write(_) | Self::Commute(_) => Self::Write(w),
 
             // Register write
             Self::ReadObsolete(r) | Self::ReadObsoleteWrite(r, _) => Self::ReadObsoleteWrite(r, w),
@@ -85,6 +123,30 @@ impl LogVar {
         };
     }
 
+    /// Register a commutative update and potentially upgrade the state.
+    pub fn commute(&mut self, f: Commutation) {
+        let this = std::mem::replace(self, Self::Commute(Vec::new()));
+
+        *self = match this {
+            // Delay the update until commit.
+            Self::Commute(mut fs) => {
+                fs.push(f);
+                Self::Commute(fs)
+            }
+
+            // The written value is known, update it right away.
+            Self::Write(w) => Self::Write(f(&w)),
+            Self::ReadObsoleteWrite(r, w) => Self::ReadObsoleteWrite(r, f(&w)),
+            Self::ReadWrite(r, w) => Self::ReadWrite(r, f(&w)),
+
+            // Update the read value.
+            Self::Read(r) | Self::ReadObsolete(r) => {
+                let w = f(&r);
+                Self::ReadWrite(r, w)
+            }
+        };
+    }
+
     /// Turn `self` into an obsolete version.
     pub fn obsolete(self) -> Option<LogVar> {
         self.into_read_value().map(LogVar::ReadObsolete)
@@ -97,14 +159,37 @@ impl LogVar {
             | LogVar::ReadWrite(v, _)
             | LogVar::ReadObsolete(v)
             | LogVar::ReadObsoleteWrite(v, _) => Some(v),
-            LogVar::Write(_) => None,
+            LogVar::Write(_) | LogVar::Commute(_) => None,
         }
     }
 }
 
+/// Apply commutative updates to a value, in order.
+pub fn apply(fs: &[Commutation], value: &ArcAny) -> ArcAny {
+    fs.iter().fold(value.clone(), |value, f| f(&value))
+}
+
 /// Test if writes are ignored, when a var is set to obsolete.
 #[test]
 fn test_write_obsolete_ignore() {
     let t = LogVar::Write(Arc::new(42)).obsolete();
     assert!(t.is_none());
+
+    let t = LogVar::Commute(Vec::new()).obsolete();
+    assert!(t.is_none());
+}
+
+/// Test if commutations are delayed, unless a value is already logged.
+#[test]
+fn test_commute() {
+    let double: Commutation = Arc::new(|v| Arc::new(v.downcast_ref::<i32>().unwrap() * 2));
+
+    let mut t = LogVar::Commute(vec![double.clone()]);
+    t.commute(double.clone());
+    assert!(matches!(t, LogVar::Commute(ref fs) if fs.len() == 2));
+
+    let mut t = LogVar::Read(Arc::new(21));
+    t.commute(double);
+    assert!(matches!(t, LogVar::ReadWrite(..)));
+    assert_eq!(t.read().downcast_ref::<i32>(), Some(&42));
 }
@@ -4,28 +4,47 @@ pub mod log_var;
 
 cfg_if::cfg_if! {
     if #[cfg(feature = "hash-registers")] {
-        use std::collections::hash_map::Entry;
+        use std::collections::hash_map::{Entry, OccupiedEntry};
 
         use rustc_hash::FxHashMap;
     } else {
-        use std::collections::{btree_map::Entry, BTreeMap};
+        use std::collections::{
+            btree_map::{Entry, OccupiedEntry},
+            BTreeMap,
+        };
     }
 }
 
+use parking_lot::RwLock;
 use std::any::Any;
 use std::cell::Cell;
 use std::mem;
 use std::sync::Arc;
 
-use crate::result::{StmClosureResult, StmError};
-use crate::tvar::{TVar, VarControlBlock};
+#[cfg(feature = "event-log")]
+use crate::event_log::{self, Attempt, Outcome};
+#[cfg(feature = "profiling")]
+use crate::histogram::Recorder;
+#[cfg(feature = "wait-on-retry")]
+use crate::parked::Parking;
+use crate::result::{FailureReason, StmClosureResult, StmError};
+#[cfg(feature = "profiling")]
+use crate::stats::STATS;
+#[cfg(feature = "early-conflict-detection")]
+use crate::tvar::same_value;
+#[cfg(feature = "profiling")]
+use crate::tvar::VarId;
+use crate::tvar::{TVar, VarRef};
+#[cfg(feature = "watchdog")]
+use crate::watchdog::Watch;
 use crate::{TransactionClosureResult, TransactionError, TransactionResult};
 
 #[cfg(feature = "wait-on-retry")]
 use control_block::ControlBlock;
-use log_var::LogVar;
+use log_var::{downcast_arc, downcast_ref, ArcAny, Commutation, LogVar};
 
 thread_local!(static TRANSACTION_RUNNING: Cell<bool> = const { Cell::new(false) });
+thread_local!(static TRANSACTION_NAME: Cell<Option<&'static str>> = const { Cell::new(None) });
 
 /// `TransactionGuard` checks against nested STM calls.
 ///
@@ -33,20 +52,40 @@ thread_local!(static TRANSACTION_RUNNING: Cell<bool> = const { Cell::new(false)
 struct TransactionGuard;
 
 impl TransactionGuard {
-    pub fn new() -> TransactionGuard {
+    pub fn new(name: Option<&'static str>) -> TransactionGuard {
         TRANSACTION_RUNNING.with(|t| {
-            assert!(!t.get(), "STM: Nested Transaction");
+            if t.get() {
+                let outer = TRANSACTION_NAME.get();
+                panic!(
+                    "STM: Nested Transaction {} inside {}",
+                    label(name),
+                    label(outer)
+                );
+            }
             t.set(true);
         });
+        TRANSACTION_NAME.set(name);
         TransactionGuard
     }
 }
 
+/// Enter the span covering all the attempts of a transaction.
+#[cfg(feature = "tracing")]
+fn transaction_span(name: Option<&'static str>) -> tracing::span::EnteredSpan {
+    tracing::debug_span!("atomically", name).entered()
+}
+
+/// Format the name of a transaction for diagnostics.
+fn label(name: Option<&'static str>) -> String {
+    name.map_or_else(|| String::from("<unnamed>"), |name| format!("`{name}`"))
+}
+
 impl Drop for TransactionGuard {
     fn drop(&mut self) {
         TRANSACTION_RUNNING.with(|t| {
             t.set(false);
         });
+        TRANSACTION_NAME.set(None);
     }
 }
 
@@ -66,6 +105,40 @@ pub struct TransactionTallies {
     pub n_redundant_read: std::sync::atomic::AtomicUsize,
     pub n_read_after_write: std::sync::atomic::AtomicUsize,
     pub n_write: std::sync::atomic::AtomicUsize,
+    /// Number of failed validations, for each variable that failed them.
+    pub conflicts: parking_lot::Mutex<std::collections::HashMap<VarId, usize>>,
+}
+
+#[cfg(feature = "profiling")]
+impl TransactionTallies {
+    /// Return the `n` variables that failed validation most often, with their number of
+    /// failures, in decreasing order.
+    ///
+    /// ```
+    /// # use fast_stm::*;
+    /// let balance = TVar::with_name(0, "balance");
+    /// let (_, tallies) = Transaction::profile_with(|trans| balance.modify(trans, |x| x + 1));
+    ///
+    /// for (var, count) in tallies.hottest_conflicts(10) {
+    ///     println!("{var}: {count} conflicts");
+    /// }
+    /// ```
+    pub fn hottest_conflicts(&self, n: usize) -> Vec<(VarId, usize)> {
+        let mut conflicts: Vec<_> = self
+            .conflicts
+            .lock()
+            .iter()
+            .map(|(var, count)| (*var, *count))
+            .collect();
+        conflicts.sort_by(|(v1, c1), (v2, c2)| c2.cmp(c1).then(v1.cmp(v2)));
+        conflicts.truncate(n);
+        conflicts
+    }
+
+    /// Record a failed validation of `var`.
+    fn record_conflict(&self, var: &VarRef) {
+        *self.conflicts.lock().entry(var.id()).or_default() += 1;
+    }
 }
 
 #[cfg(feature = "profiling")]
@@ -101,6 +174,10 @@ impl std::ops::AddAssign for TransactionTallies {
             rhs.n_write.load(std::sync::atomic::Ordering::Relaxed),
             std::sync::atomic::Ordering::Relaxed,
         );
+        let mut conflicts = self.conflicts.lock();
+        for (var, count) in rhs.conflicts.into_inner() {
+            *conflicts.entry(var).or_default() += count;
+        }
     }
 }
 
@@ -117,31 +194,36 @@ impl std::iter::Sum for TransactionTallies {
 // -- Transactions
 
 #[cfg(not(feature = "hash-registers"))]
-pub(crate) type RegisterType = BTreeMap<Arc<VarControlBlock>, LogVar>;
+pub(crate) type RegisterType = BTreeMap<VarRef, LogVar>;
 #[cfg(feature = "hash-registers")]
-pub(crate) type RegisterType = FxHashMap<*const VarControlBlock, LogVar>;
+pub(crate) type RegisterType = FxHashMap<VarRef, LogVar>;
 
 /// Transaction tracks all the read and written variables.
 ///
 /// It is used for checking vars, to ensure atomicity.
+#[derive(Default)]
 pub struct Transaction {
-    /// Map of all vars that map the `VarControlBlock` of a var to a `LogVar`.
-    /// The `VarControlBlock` is unique because it uses it's address for comparing.
+    /// Map of all vars that map the `VarRef` of a var to a `LogVar`.
+    /// The `VarRef` is unique because it uses it's address for comparing.
     ///
     /// The logs need to be accessed in a order to prevend dead-locks on locking.
     vars: RegisterType,
+    /// Label of the transaction, used for diagnostics.
+    name: Option<&'static str>,
+    #[cfg(feature = "profiling")]
+    recorder: Recorder,
     #[cfg(feature = "profiling")]
     tallies: TransactionTallies,
-}
-
-impl Default for Transaction {
-    fn default() -> Self {
-        Self {
-            vars: RegisterType::default(),
-            #[cfg(feature = "profiling")]
-            tallies: TransactionTallies::default(),
-        }
-    }
+    /// Number of runs of the transaction closure, used to label traces.
+    #[cfg(feature = "tracing")]
+    attempts: u64,
+    /// Set when the current attempt failed because `early-conflict-detection` found a change.
+    #[cfg(feature = "early-conflict-detection")]
+    early_conflict: bool,
+    #[cfg(feature = "watchdog")]
+    watch: Watch,
+    #[cfg(feature = "event-log")]
+    attempt: Attempt,
 }
 
 /// Public API
@@ -164,7 +246,8 @@ impl Transaction {
     /// `with_control` takes another control function, that
     /// can steer the control flow and possible terminate early.
     ///
-    /// `control` can react to counters, timeouts or external inputs.
+    /// `control` is called with the reason of each failed attempt, including commits that fail
+    /// validation. It can react to this reason, counters, timeouts or external inputs.
     ///
     /// It allows the user to fall back to another strategy, like a global lock
     /// in the case of too much contention.
@@ -172,40 +255,81 @@ impl Transaction {
     /// Please not, that the transaction may still infinitely wait for changes when `retry` is
     /// called and `control` does not abort.
     /// If you need a timeout, another thread should signal this through a [`TVar`].
-    pub fn with_control<T, F, C>(mut control: C, f: F) -> Option<T>
+    pub fn with_control<T, F, C>(control: C, f: F) -> Option<T>
     where
         F: Fn(&mut Transaction) -> StmClosureResult<T>,
-        C: FnMut(StmError) -> TransactionControl,
+        C: FnMut(FailureReason) -> TransactionControl,
     {
-        let _guard = TransactionGuard::new();
+        Transaction::with_control_and_name(None, control, f)
+    }
+
+    /// Run a function with a transaction labeled `name`.
+    ///
+    /// It is equivalent to `atomically_named`.
+    pub fn with_name<T, F>(name: &'static str, f: F) -> T
+    where
+        F: Fn(&mut Transaction) -> StmClosureResult<T>,
+    {
+        match Transaction::with_control_and_name(Some(name), |_| TransactionControl::Retry, f) {
+            Some(t) => t,
+            None => unreachable!(),
+        }
+    }
+
+    /// Return the label of the transaction, if any.
+    pub fn name(&self) -> Option<&'static str> {
+        self.name
+    }
+
+    fn with_control_and_name<T, F, C>(name: Option<&'static str>, mut control: C, f: F) -> Option<T>
+    where
+        F: Fn(&mut Transaction) -> StmClosureResult<T>,
+        C: FnMut(FailureReason) -> TransactionControl,
+    {
+        let _guard = TransactionGuard::new(name);
+        #[cfg(feature = "tracing")]
+        let _span = transaction_span(name);
 
         // create a log guard for initializing and cleaning up
         // the log
-        let mut transaction = Transaction::default();
+        let mut transaction = Transaction {
+            name,
+            ..Transaction::default()
+        };
 
         // loop until success
         loop {
+            #[cfg(feature = "profiling")]
+            transaction.record_attempt();
+            #[cfg(feature = "tracing")]
+            let _attempt = transaction.attempt_span();
             // run the computation
-            match f(&mut transaction) {
+            let reason = match f(&mut transaction) {
                 // on success exit loop
                 Ok(t) => {
                     if transaction.commit() {
                         return Some(t);
                     }
+                    FailureReason::CommitConflict
                 }
 
                 Err(e) => {
-                    // Check if the user wants to abort the transaction.
-                    if let TransactionControl::Abort = control(e) {
-                        return None;
-                    }
-
-                    // on retry wait for changes
-                    #[cfg(feature = "wait-on-retry")]
-                    if let StmError::Retry = e {
-                        transaction.wait_for_change();
-                    }
+                    transaction.record_error(e);
+                    transaction.failure_reason(e)
                 }
+            };
+
+            // Check if the user wants to abort the transaction.
+            if let TransactionControl::Abort = control(reason) {
+                #[cfg(feature = "tracing")]
+                tracing::debug!(?reason, "transaction aborted");
+                return None;
+            }
+
+            // on retry wait for changes
+            #[cfg(feature = "wait-on-retry")]
+            if let FailureReason::Retry = reason {
+                transaction.wait_for_change();
             }
 
             // clear log before retrying computation
@@ -222,7 +346,9 @@ impl Transaction {
     where
         F: Fn(&mut Transaction) -> TransactionClosureResult<T, E>,
     {
-        let _guard = TransactionGuard::new();
+        let _guard = TransactionGuard::new(None);
+        #[cfg(feature = "tracing")]
+        let _span = transaction_span(None);
 
         // create a log guard for initializing and cleaning up
         // the log
@@ -230,6 +356,10 @@ impl Transaction {
 
         // loop until success
         loop {
+            #[cfg(feature = "profiling")]
+            transaction.record_attempt();
+            #[cfg(feature = "tracing")]
+            let _attempt = transaction.attempt_span();
             // run the computation
             match f(&mut transaction) {
                 // on success exit loop
@@ -241,9 +371,14 @@ impl Transaction {
                 // on error,
                 Err(e) => match e {
                     // abort and return the error
-                    TransactionError::Abort(err) => return Err(err),
+                    TransactionError::Abort(err) => {
+                        transaction.record_cancel();
+                        return Err(err);
+                    }
                     // retry
-                    TransactionError::Stm(_) => {
+                    TransactionError::Stm(err) => {
+                        transaction.record_error(err);
+
                         #[cfg(feature = "wait-on-retry")]
                         transaction.wait_for_change();
                     }
@@ -260,7 +395,8 @@ impl Transaction {
     /// `with_control` takes another control function, that
     /// can steer the control flow and possible terminate early.
     ///
-    /// `control` can react to counters, timeouts or external inputs.
+    /// `control` is called with the reason of each failed attempt, including commits that fail
+    /// validation. It can react to this reason, counters, timeouts or external inputs.
     ///
     /// It allows the user to fall back to another strategy, like a global lock
     /// in the case of too much contention.
@@ -271,9 +407,11 @@ impl Transaction {
     pub fn with_control_and_err<T, F, C, E>(mut control: C, f: F) -> TransactionResult<T, E>
     where
         F: Fn(&mut Transaction) -> TransactionClosureResult<T, E>,
-        C: FnMut(StmError) -> TransactionControl,
+        C: FnMut(FailureReason) -> TransactionControl,
     {
-        let _guard = TransactionGuard::new();
+        let _guard = TransactionGuard::new(None);
+        #[cfg(feature = "tracing")]
+        let _span = transaction_span(None);
 
         // create a log guard for initializing and cleaning up
         // the log
@@ -281,34 +419,41 @@ impl Transaction {
 
         // loop until success
         loop {
+            #[cfg(feature = "profiling")]
+            transaction.record_attempt();
+            #[cfg(feature = "tracing")]
+            let _attempt = transaction.attempt_span();
             // run the computation
-            match f(&mut transaction) {
+            let reason = match f(&mut transaction) {
                 // on success exit loop
                 Ok(t) => {
                     if transaction.commit() {
                         return TransactionResult::Validated(t);
                     }
+                    FailureReason::CommitConflict
                 }
 
-                Err(e) => {
-                    match e {
-                        TransactionError::Abort(err) => {
-                            return TransactionResult::Cancelled(err);
-                        }
-                        TransactionError::Stm(err) => {
-                            // Check if the user wants to abort the transaction.
-                            if let TransactionControl::Abort = control(err) {
-                                return TransactionResult::Abandoned;
-                            }
-
-                            // on retry wait for changes
-                            #[cfg(feature = "wait-on-retry")]
-                            if let StmError::Retry = err {
-                                transaction.wait_for_change();
-                            }
-                        }
-                    }
+                Err(TransactionError::Abort(err)) => {
+                    transaction.record_cancel();
+                    return TransactionResult::Cancelled(err);
+                }
+                Err(TransactionError::Stm(err)) => {
+                    transaction.record_error(err);
+                    transaction.failure_reason(err)
                 }
+            };
+
+            // Check if the user wants to abort the transaction.
+            if let TransactionControl::Abort = control(reason) {
+                #[cfg(feature = "tracing")]
+                tracing::debug!(?reason, "transaction aborted");
+                return TransactionResult::Abandoned;
+            }
+
+            // on retry wait for changes
+            #[cfg(feature = "wait-on-retry")]
+            if let FailureReason::Retry = reason {
+                transaction.wait_for_change();
             }
 
             // clear log before retrying computation
@@ -338,7 +483,8 @@ impl Transaction {
     /// `with_control` takes another control function, that
     /// can steer the control flow and possible terminate early.
     ///
-    /// `control` can react to counters, timeouts or external inputs.
+    /// `control` is called with the reason of each failed attempt, including commits that fail
+    /// validation. It can react to this reason, counters, timeouts or external inputs.
     ///
     /// It allows the user to fall back to another strategy, like a global lock
     /// in the case of too much contention.
@@ -349,9 +495,11 @@ impl Transaction {
     pub fn profile_with_control<T, F, C>(mut control: C, f: F) -> (Option<T>, TransactionTallies)
     where
         F: Fn(&mut Transaction) -> StmClosureResult<T>,
-        C: FnMut(StmError) -> TransactionControl,
+        C: FnMut(FailureReason) -> TransactionControl,
     {
-        let _guard = TransactionGuard::new();
+        let _guard = TransactionGuard::new(None);
+        #[cfg(feature = "tracing")]
+        let _span = transaction_span(None);
 
         // create a log guard for initializing and cleaning up
         // the log
@@ -359,21 +507,24 @@ impl Transaction {
 
         // loop until success
         loop {
+            transaction.record_attempt();
+            #[cfg(feature = "tracing")]
+            let _attempt = transaction.attempt_span();
             transaction
                 .tallies
                 .n_attempts
                 .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
             // run the computation
-            match f(&mut transaction) {
+            let reason = match f(&mut transaction) {
                 // on success exit loop
                 Ok(t) => {
                     if transaction.commit() {
                         return (Some(t), transaction.tallies);
                     }
+                    FailureReason::CommitConflict
                 }
 
                 Err(e) => {
-                    // Check if the user wants to abort the transaction.
                     match e {
                         StmError::Failure => {
                             transaction
@@ -388,17 +539,22 @@ impl Transaction {
                                 .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
                         }
                     }
+                    transaction.record_error(e);
+                    transaction.failure_reason(e)
+                }
+            };
 
-                    if let TransactionControl::Abort = control(e) {
-                        return (None, transaction.tallies);
-                    }
+            // Check if the user wants to abort the transaction.
+            if let TransactionControl::Abort = control(reason) {
+                #[cfg(feature = "tracing")]
+                tracing::debug!(?reason, "transaction aborted");
+                return (None, transaction.tallies);
+            }
 
-                    // on retry wait for changes
-                    #[cfg(feature = "wait-on-retry")]
-                    if let StmError::Retry = e {
-                        transaction.wait_for_change();
-                    }
-                }
+            // on retry wait for changes
+            #[cfg(feature = "wait-on-retry")]
+            if let FailureReason::Retry = reason {
+                transaction.wait_for_change();
             }
 
             // clear log before retrying computation
@@ -415,7 +571,9 @@ impl Transaction {
     where
         F: Fn(&mut Transaction) -> TransactionClosureResult<T, E>,
     {
-        let _guard = TransactionGuard::new();
+        let _guard = TransactionGuard::new(None);
+        #[cfg(feature = "tracing")]
+        let _span = transaction_span(None);
 
         // create a log guard for initializing and cleaning up
         // the log
@@ -423,6 +581,9 @@ impl Transaction {
 
         // loop until success
         loop {
+            transaction.record_attempt();
+            #[cfg(feature = "tracing")]
+            let _attempt = transaction.attempt_span();
             transaction
                 .tallies
                 .n_attempts
@@ -438,7 +599,10 @@ impl Transaction {
                 // on error,
                 Err(e) => match e {
                     // abort and return the error
-                    TransactionError::Abort(err) => return (Err(err), transaction.tallies),
+                    TransactionError::Abort(err) => {
+                        transaction.record_cancel();
+                        return (Err(err), transaction.tallies);
+                    }
                     // retry
                     TransactionError::Stm(err) => {
                         match err {
@@ -455,6 +619,7 @@ impl Transaction {
                                     .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
                             }
                         }
+                        transaction.record_error(err);
                         #[cfg(feature = "wait-on-retry")]
                         transaction.wait_for_change();
                     }
@@ -471,7 +636,8 @@ impl Transaction {
     /// `with_control` takes another control function, that
     /// can steer the control flow and possible terminate early.
     ///
-    /// `control` can react to counters, timeouts or external inputs.
+    /// `control` is called with the reason of each failed attempt, including commits that fail
+    /// validation. It can react to this reason, counters, timeouts or external inputs.
     ///
     /// It allows the user to fall back to another strategy, like a global lock
     /// in the case of too much contention.
@@ -485,9 +651,11 @@ impl Transaction {
     ) -> (TransactionResult<T, E>, TransactionTallies)
     where
         F: Fn(&mut Transaction) -> TransactionClosureResult<T, E>,
-        C: FnMut(StmError) -> TransactionControl,
+        C: FnMut(FailureReason) -> TransactionControl,
     {
-        let _guard = TransactionGuard::new();
+        let _guard = TransactionGuard::new(None);
+        #[cfg(feature = "tracing")]
+        let _span = transaction_span(None);
 
         // create a log guard for initializing and cleaning up
         // the log
@@ -495,53 +663,58 @@ impl Transaction {
 
         // loop until success
         loop {
+            transaction.record_attempt();
+            #[cfg(feature = "tracing")]
+            let _attempt = transaction.attempt_span();
             transaction
                 .tallies
                 .n_attempts
                 .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
             // run the computation
-            match f(&mut transaction) {
+            let reason = match f(&mut transaction) {
                 // on success exit loop
                 Ok(t) => {
                     if transaction.commit() {
                         return (TransactionResult::Validated(t), transaction.tallies);
                     }
+                    FailureReason::CommitConflict
                 }
 
-                Err(e) => {
-                    match e {
-                        TransactionError::Abort(err) => {
-                            return (TransactionResult::Cancelled(err), transaction.tallies);
+                Err(TransactionError::Abort(err)) => {
+                    transaction.record_cancel();
+                    return (TransactionResult::Cancelled(err), transaction.tallies);
+                }
+                Err(TransactionError::Stm(err)) => {
+                    match err {
+                        StmError::Failure => {
+                            transaction
+                                .tallies
+                                .n_error
+                                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
                         }
-                        TransactionError::Stm(err) => {
-                            match err {
-                                StmError::Failure => {
-                                    transaction
-                                        .tallies
-                                        .n_error
-                                        .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                                }
-                                StmError::Retry => {
-                                    transaction
-                                        .tallies
-                                        .n_retry
-                                        .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                                }
-                            }
-
-                            // Check if the user wants to abort the transaction.
-                            if let TransactionControl::Abort = control(err) {
-                                return (TransactionResult::Abandoned, transaction.tallies);
-                            }
-
-                            // on retry wait for changes
-                            #[cfg(feature = "wait-on-retry")]
-                            if let StmError::Retry = err {
-                                transaction.wait_for_change();
-                            }
+                        StmError::Retry => {
+                            transaction
+                                .tallies
+                                .n_retry
+                                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
                         }
                     }
+                    transaction.record_error(err);
+                    transaction.failure_reason(err)
                 }
+            };
+
+            // Check if the user wants to abort the transaction.
+            if let TransactionControl::Abort = control(reason) {
+                #[cfg(feature = "tracing")]
+                tracing::debug!(?reason, "transaction aborted");
+                return (TransactionResult::Abandoned, transaction.tallies);
+            }
+
+            // on retry wait for changes
+            #[cfg(feature = "wait-on-retry")]
+            if let FailureReason::Retry = reason {
+                transaction.wait_for_change();
             }
 
             // clear log before retrying computation
@@ -561,102 +734,37 @@ impl Transaction {
     /// without running into infinite loops.
     /// Just the commit of wrong values is prevented by STM.
     pub fn read<T: Send + Sync + Any + Clone>(&mut self, var: &TVar<T>) -> StmClosureResult<T> {
-        #[cfg(feature = "profiling")]
-        self.tallies
-            .n_read
-            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-        let ctrl = var.control_block().clone();
-        // Check if the same var was written before.
-        #[cfg(not(feature = "hash-registers"))]
-        let key = ctrl;
-        #[cfg(feature = "hash-registers")]
-        let key = Arc::as_ptr(&ctrl);
-        let value = match self.vars.entry(key) {
-            // If the variable has been accessed before, then load that value.
-            #[cfg(feature = "early-conflict-detection")]
-            Entry::Occupied(mut entry) => {
-                let log = entry.get_mut();
-                // if we previously read the var, check for value change
-                if let LogVar::Read(v) = log {
-                    #[cfg(feature = "profiling")]
-                    self.tallies
-                        .n_redundant_read
-                        .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                    let crt_v = var.read_ref_atomic();
-                    if !Arc::ptr_eq(v, &crt_v) {
-                        return Err(StmError::Failure);
-                    }
-                }
-                #[cfg(feature = "profiling")]
-                if let LogVar::Write(_) = log {
-                    self.tallies
-                        .n_read_after_write
-                        .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                }
-                log.read()
-            }
-            #[cfg(not(feature = "early-conflict-detection"))]
-            Entry::Occupied(mut entry) => {
-                #[cfg(feature = "profiling")]
-                {
-                    let log = entry.get();
-                    if let LogVar::Read(_) = log {
-                        self.tallies
-                            .n_redundant_read
-                            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                    } else if let LogVar::Write(_) = log {
-                        self.tallies
-                            .n_read_after_write
-                            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                    }
-                }
-
-                entry.get_mut().read()
-            }
-
-            // Else load the variable statically.
-            Entry::Vacant(entry) => {
-                // Read the value from the var.
-                let value = var.read_ref_atomic();
-
-                // Store in in an entry.
-                entry.insert(LogVar::Read(value.clone()));
-                value
-            }
-        };
+        self.read_arc(var).map(|value| T::clone(&value))
+    }
 
-        Ok(Transaction::downcast(value))
+    /// Read a variable and return a shared reference to its value.
+    ///
+    /// Unlike `read`, the value is not cloned, so `T` does not need to implement `Clone`. The
+    /// same consistency caveats apply.
+    pub fn read_arc<T: Send + Sync + Any>(&mut self, var: &TVar<T>) -> StmClosureResult<Arc<T>> {
+        self.read_value(var.var_ref(), &var.control_block().value)
     }
 
     /// Write a variable.
     ///
     /// The write is not immediately visible to other threads,
     /// but atomically commited at the end of the computation.
-    pub fn write<T: Any + Send + Sync + Clone>(
+    pub fn write<T: Any + Send + Sync>(&mut self, var: &TVar<T>, value: T) -> StmClosureResult<()> {
+        self.write_value(var.var_ref(), Arc::new(value));
+
+        // For now always succeeds, but that may change later.
+        Ok(())
+    }
+
+    /// Write a variable with a value that is already stored in an `Arc`.
+    ///
+    /// The `Arc` is stored in the log as is, without copying the value.
+    pub fn write_arc<T: Any + Send + Sync>(
         &mut self,
         var: &TVar<T>,
-        value: T,
+        value: Arc<T>,
     ) -> StmClosureResult<()> {
-        #[cfg(feature = "profiling")]
-        self.tallies
-            .n_write
-            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-        // box the value
-        let boxed = Arc::new(value);
-
-        // new control block
-        let ctrl = var.control_block().clone();
-        // update or create new entry
-        #[cfg(not(feature = "hash-registers"))]
-        let key = ctrl;
-        #[cfg(feature = "hash-registers")]
-        let key = Arc::as_ptr(&ctrl);
-        match self.vars.entry(key) {
-            Entry::Occupied(mut entry) => entry.get_mut().write(boxed),
-            Entry::Vacant(entry) => {
-                entry.insert(LogVar::Write(boxed));
-            }
-        }
+        self.write_value(var.var_ref(), value);
 
         // For now always succeeds, but that may change later.
         Ok(())
@@ -676,74 +784,28 @@ impl Transaction {
     where
         F: FnOnce(T) -> T,
     {
-        #[cfg(feature = "profiling")]
-        self.tallies
-            .n_write
-            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-
-        // new control block
-        let ctrl = var.control_block().clone();
-        // update or create new entry
-        #[cfg(not(feature = "hash-registers"))]
-        let key = ctrl;
-        #[cfg(feature = "hash-registers")]
-        let key = Arc::as_ptr(&ctrl);
-        match self.vars.entry(key) {
-            // If the variable has been accessed before, then load that value.
-            #[cfg(feature = "early-conflict-detection")]
-            Entry::Occupied(mut entry) => {
-                let log = entry.get_mut();
-                // if we previously read the var, check for value change
-                if let LogVar::Read(v) = log {
-                    #[cfg(feature = "profiling")]
-                    self.tallies
-                        .n_redundant_read
-                        .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                    let crt_v = var.read_ref_atomic();
-                    if !Arc::ptr_eq(v, &crt_v) {
-                        return Err(StmError::Failure);
-                    }
-                }
-                #[cfg(feature = "profiling")]
-                if let LogVar::Write(_) = log {
-                    self.tallies
-                        .n_read_after_write
-                        .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                }
-                let value = Transaction::downcast(log.read());
-                let boxed = Arc::new(f(value));
-                entry.get_mut().write(boxed);
-            }
-            #[cfg(not(feature = "early-conflict-detection"))]
-            Entry::Occupied(mut entry) => {
-                #[cfg(feature = "profiling")]
-                {
-                    let log = entry.get();
-                    if let LogVar::Read(_) = log {
-                        self.tallies
-                            .n_redundant_read
-                            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                    } else if let LogVar::Write(_) = log {
-                        self.tallies
-                            .n_read_after_write
-                            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                    }
-                }
-
-                let value = Transaction::downcast(entry.get_mut().read());
-                let boxed = Arc::new(f(value));
-                entry.get_mut().write(boxed);
-            }
-            Entry::Vacant(entry) => {
-                // Read the value from the var.
-                let value = var.read_ref_atomic();
-                let boxed = Arc::new(f(Transaction::downcast(value.clone())));
-                entry.insert(LogVar::ReadWrite(value, boxed));
-            }
-        }
+        self.update_value(var.var_ref(), &var.control_block().value, |value| {
+            Arc::new(f(T::clone(value)))
+        })
+        .map(|_| ())
+    }
 
-        // For now always succeeds, but that may change later.
-        Ok(())
+    /// Modify a variable, computing the new value from a reference to the current one.
+    ///
+    /// Unlike `modify`, the current value is not cloned, so `T` does not need to implement
+    /// `Clone`.
+    pub fn modify_with<T: Any + Send + Sync, F>(
+        &mut self,
+        var: &TVar<T>,
+        f: F,
+    ) -> StmClosureResult<()>
+    where
+        F: FnOnce(&T) -> T,
+    {
+        self.update_value(var.var_ref(), &var.control_block().value, |value| {
+            Arc::new(f(value))
+        })
+        .map(|_| ())
     }
 
     /// Replace a variable, returning the old value.
@@ -757,76 +819,43 @@ impl Transaction {
         var: &TVar<T>,
         value: T,
     ) -> StmClosureResult<T> {
-        #[cfg(feature = "profiling")]
-        self.tallies
-            .n_write
-            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-        // box the value
-        let boxed = Arc::new(value);
-
-        // new control block
-        let ctrl = var.control_block().clone();
-        // update or create new entry
-        #[cfg(not(feature = "hash-registers"))]
-        let key = ctrl;
-        #[cfg(feature = "hash-registers")]
-        let key = Arc::as_ptr(&ctrl);
-        let value = match self.vars.entry(key) {
-            // If the variable has been accessed before, then load that value.
-            #[cfg(feature = "early-conflict-detection")]
-            Entry::Occupied(mut entry) => {
-                let log = entry.get_mut();
-                // if we previously read the var, check for value change
-                if let LogVar::Read(v) = log {
-                    #[cfg(feature = "profiling")]
-                    self.tallies
-                        .n_redundant_read
-                        .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                    let crt_v = var.read_ref_atomic();
-                    if !Arc::ptr_eq(v, &crt_v) {
-                        return Err(StmError::Failure);
-                    }
-                }
-                #[cfg(feature = "profiling")]
-                if let LogVar::Write(_) = log {
-                    self.tallies
-                        .n_read_after_write
-                        .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                }
-                let value = log.read();
-                entry.get_mut().write(boxed);
-                value
-            }
-            #[cfg(not(feature = "early-conflict-detection"))]
-            Entry::Occupied(mut entry) => {
-                #[cfg(feature = "profiling")]
-                {
-                    let log = entry.get();
-                    if let LogVar::Read(_) = log {
-                        self.tallies
-                            .n_redundant_read
-                            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                    } else if let LogVar::Write(_) = log {
-                        self.tallies
-                            .n_read_after_write
-                            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                    }
-                }
+        self.update_value(var.var_ref(), &var.control_block().value, |_| {
+            Arc::new(value)
+        })
+        .map(|value| T::clone(&value))
+    }
 
-                let value = entry.get_mut().read();
-                entry.get_mut().write(boxed);
-                value
-            }
-            Entry::Vacant(entry) => {
-                // Read the value from the var.
-                let value = var.read_ref_atomic();
-                entry.insert(LogVar::ReadWrite(value.clone(), boxed));
-                value
-            }
-        };
+    /// Update a variable using a commutative function.
+    ///
+    /// Unlike `modify`, `f` is not applied right away: it is registered in the log, and applied
+    /// to the value of the variable when committing, while holding the lock of the variable.
+    /// Since the update does not depend on the value seen by the transaction, it never causes
+    /// the transaction to fail. This is useful for updates that commute with each other, like
+    /// additions or insertions into a set.
+    ///
+    /// If the variable is read by the same transaction, before or after calling `commute`, the
+    /// update is applied to the read value and behaves like `modify`.
+    ///
+    /// `f` should be cheap, since it may run while other variables are locked, and must not
+    /// access other `TVar`s.
+    ///
+    /// ```
+    /// # use fast_stm::*;
+    /// let hits = TVar::new(0);
+    ///
+    /// atomically(|trans| hits.commute(trans, |x| x + 1));
+    ///
+    /// assert_eq!(hits.read_atomic(), 1);
+    /// ```
+    pub fn commute<T, F>(&mut self, var: &TVar<T>, f: F) -> StmClosureResult<()>
+    where
+        T: Any + Send + Sync + Clone,
+        F: Fn(T) -> T + Send + Sync + 'static,
+    {
+        self.commute_value(var.var_ref(), move |value: &T| f(value.clone()));
 
         // For now always succeeds, but that may change later.
-        Ok(Transaction::downcast(value))
+        Ok(())
     }
 
     /// Combine two calculations. When one blocks with `retry`,
@@ -841,6 +870,7 @@ impl Transaction {
         F2: Fn(&mut Transaction) -> StmClosureResult<T>,
     {
         // Create a backup of the log.
+        #[allow(clippy::mutable_key_type)]
         let mut copy = self.vars.clone();
         // let mut copy = Transaction {
         //     vars: self.vars.clone(),
@@ -852,6 +882,9 @@ impl Transaction {
         match f {
             // Run other on manual retry call.
             Err(StmError::Retry) => {
+                #[cfg(feature = "tracing")]
+                tracing::trace!("first branch retried, running the second one");
+
                 // swap, so that self is the current run
                 mem::swap(&mut self.vars, &mut copy);
 
@@ -876,16 +909,272 @@ impl Transaction {
 
 /// Internal routines
 impl Transaction {
-    #[allow(clippy::needless_pass_by_value)]
-    /// Perform a downcast on a var.
-    fn downcast<T: Any + Clone>(var: Arc<dyn Any>) -> T {
-        match var.downcast_ref::<T>() {
-            Some(s) => s.clone(),
-            None => unreachable!("TVar has wrong type"),
+    /// Register the start of an attempt in the global statistics and histograms.
+    #[cfg(feature = "profiling")]
+    fn record_attempt(&mut self) {
+        STATS.attempts.incr();
+        self.recorder.attempt(self.name);
+    }
+
+    /// Record a failed attempt in the global statistics, traces and event log.
+    #[cfg_attr(
+        not(any(feature = "profiling", feature = "event-log")),
+        allow(clippy::unused_self)
+    )]
+    #[cfg_attr(
+        not(any(feature = "profiling", feature = "tracing", feature = "event-log")),
+        allow(unused_variables)
+    )]
+    fn record_error(&self, err: StmError) {
+        #[cfg(feature = "profiling")]
+        match self.failure_reason(err) {
+            FailureReason::Retry => STATS.retries.incr(),
+            FailureReason::EarlyConflict => STATS.early_conflicts.incr(),
+            FailureReason::CommitConflict | FailureReason::Failure => STATS.failures.incr(),
+        }
+        #[cfg(feature = "tracing")]
+        tracing::trace!(error = ?err, "attempt failed");
+        #[cfg(feature = "event-log")]
+        self.log_attempt(&[], Outcome::Failed(self.failure_reason(err)));
+    }
+
+    /// Record a call to `abort` in the traces and event log.
+    #[cfg_attr(not(feature = "event-log"), allow(clippy::unused_self))]
+    fn record_cancel(&self) {
+        #[cfg(feature = "tracing")]
+        tracing::debug!("transaction cancelled");
+        #[cfg(feature = "event-log")]
+        self.log_attempt(&[], Outcome::Cancel);
+    }
+
+    /// Write the current attempt to the event log, if one is being recorded.
+    #[cfg(feature = "event-log")]
+    fn log_attempt(&self, written: &[(&VarRef, ArcAny)], outcome: Outcome) {
+        if event_log::recording() {
+            self.attempt.record(self.name, &self.vars, written, outcome);
+        }
+    }
+
+    /// Return why the current attempt failed with `err`.
+    #[cfg_attr(not(feature = "early-conflict-detection"), allow(clippy::unused_self))]
+    fn failure_reason(&self, err: StmError) -> FailureReason {
+        match err {
+            StmError::Retry => FailureReason::Retry,
+            #[cfg(feature = "early-conflict-detection")]
+            StmError::Failure if self.early_conflict => FailureReason::EarlyConflict,
+            StmError::Failure => FailureReason::Failure,
+        }
+    }
+
+    /// Enter the span of a run of the transaction closure.
+    #[cfg(feature = "tracing")]
+    fn attempt_span(&mut self) -> tracing::span::EnteredSpan {
+        self.attempts += 1;
+        tracing::trace_span!("attempt", n = self.attempts).entered()
+    }
+
+    /// Register a failed validation of `var` when committing.
+    ///
+    /// It is called once all the variables are unlocked again.
+    #[cfg_attr(
+        not(any(feature = "profiling", feature = "event-log")),
+        allow(clippy::unused_self)
+    )]
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    fn record_commit_failure(&self, var: &VarRef, reason: &'static str) {
+        #[cfg(feature = "profiling")]
+        {
+            self.tallies.record_conflict(var);
+            STATS.commit_failures.incr();
+        }
+        #[cfg(feature = "tracing")]
+        tracing::debug!(var = %var.id(), reason, "commit failed");
+        #[cfg(feature = "event-log")]
+        self.log_attempt(&[], Outcome::Conflict(var.id()));
+    }
+
+    /// Read the value of a variable, registering it in the log.
+    ///
+    /// `cell` is the storage of `var`, accessed with its type.
+    pub(crate) fn read_value<T>(
+        &mut self,
+        var: VarRef,
+        cell: &RwLock<Arc<T>>,
+    ) -> StmClosureResult<Arc<T>>
+    where
+        T: Any + Send + Sync,
+    {
+        #[cfg(feature = "profiling")]
+        self.tallies
+            .n_read
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        match self.vars.entry(var) {
+            // If the variable has been accessed before, then load that value.
+            Entry::Occupied(entry) => Transaction::logged(
+                #[cfg(feature = "profiling")]
+                &self.tallies,
+                #[cfg(feature = "early-conflict-detection")]
+                &mut self.early_conflict,
+                entry,
+                cell,
+            )
+            .map(|log| downcast_arc(log.read())),
+
+            // Else load the variable statically.
+            Entry::Vacant(entry) => {
+                // Read the value from the var.
+                let value = cell.read().clone();
+
+                // Store in in an entry.
+                entry.insert(LogVar::Read(value.clone()));
+                Ok(value)
+            }
+        }
+    }
+
+    /// Write the value of a variable, registering it in the log.
+    pub(crate) fn write_value<T>(&mut self, var: VarRef, value: Arc<T>)
+    where
+        T: Any + Send + Sync,
+    {
+        #[cfg(feature = "profiling")]
+        self.tallies
+            .n_write
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        // update or create new entry
+        let value: ArcAny = value;
+        match self.vars.entry(var) {
+            Entry::Occupied(mut entry) => entry.get_mut().write(value),
+            Entry::Vacant(entry) => {
+                entry.insert(LogVar::Write(value));
+            }
+        }
+    }
+
+    /// Replace the value of a variable by the result of `f`, returning the previous value.
+    ///
+    /// This only performs a single lookup in the log.
+    pub(crate) fn update_value<T, F>(
+        &mut self,
+        var: VarRef,
+        cell: &RwLock<Arc<T>>,
+        f: F,
+    ) -> StmClosureResult<Arc<T>>
+    where
+        T: Any + Send + Sync,
+        F: FnOnce(&Arc<T>) -> Arc<T>,
+    {
+        #[cfg(feature = "profiling")]
+        self.tallies
+            .n_write
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        match self.vars.entry(var) {
+            // If the variable has been accessed before, then load that value.
+            Entry::Occupied(entry) => {
+                let log = Transaction::logged(
+                    #[cfg(feature = "profiling")]
+                    &self.tallies,
+                    #[cfg(feature = "early-conflict-detection")]
+                    &mut self.early_conflict,
+                    entry,
+                    cell,
+                )?;
+                let value = downcast_arc(log.read());
+                log.write(f(&value));
+                Ok(value)
+            }
+            Entry::Vacant(entry) => {
+                // Read the value from the var.
+                let value = cell.read().clone();
+                let new = f(&value);
+                entry.insert(LogVar::ReadWrite(value.clone(), new));
+                Ok(value)
+            }
+        }
+    }
+
+    /// Update a variable using a commutative function, registering it in the log.
+    ///
+    /// Unless the variable is also read by the transaction, `f` is applied to the current value
+    /// of the variable at commit.
+    pub(crate) fn commute_value<T, F>(&mut self, var: VarRef, f: F)
+    where
+        T: Any + Send + Sync,
+        F: Fn(&T) -> T + Send + Sync + 'static,
+    {
+        let f: Commutation = Arc::new(move |value| Arc::new(f(downcast_ref(value))));
+        #[cfg(feature = "profiling")]
+        self.tallies
+            .n_write
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        match self.vars.entry(var) {
+            Entry::Occupied(mut entry) => entry.get_mut().commute(f),
+            Entry::Vacant(entry) => {
+                entry.insert(LogVar::Commute(vec![f]));
+            }
         }
     }
 
+    /// Access the log of a variable that has been accessed before, in order to read it.
+    ///
+    /// With `early-conflict-detection`, this fails if the variable was read before and its
+    /// value has changed since.
+    ///
+    /// Pending commutations are resolved against the current value of the variable.
+    #[allow(clippy::elidable_lifetime_names, clippy::unnecessary_wraps)]
+    fn logged<'a, T>(
+        #[cfg(feature = "profiling")] tallies: &TransactionTallies,
+        #[cfg(feature = "early-conflict-detection")] early_conflict: &mut bool,
+        mut entry: OccupiedEntry<'a, VarRef, LogVar>,
+        cell: &RwLock<Arc<T>>,
+    ) -> StmClosureResult<&'a mut LogVar>
+    where
+        T: Any + Send + Sync,
+    {
+        #[cfg(feature = "profiling")]
+        match entry.get() {
+            LogVar::Read(_) => {
+                tallies
+                    .n_redundant_read
+                    .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            }
+            LogVar::Write(_) => {
+                tallies
+                    .n_read_after_write
+                    .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            }
+            _ => {}
+        }
+
+        // if we previously read the var, check for value change
+        #[cfg(feature = "early-conflict-detection")]
+        if let LogVar::Read(v) = entry.get() {
+            if !same_value(v, &cell.read()) {
+                #[cfg(feature = "profiling")]
+                tallies.record_conflict(entry.key());
+                #[cfg(feature = "tracing")]
+                tracing::debug!(
+                    var = %entry.key().id(),
+                    reason = "read value changed",
+                    "early conflict"
+                );
+                *early_conflict = true;
+                return Err(StmError::Failure);
+            }
+        }
+
+        // reading a commuted var makes the update depend on the current value
+        if let LogVar::Commute(fs) = entry.get() {
+            let value: ArcAny = cell.read().clone();
+            let updated = log_var::apply(fs, &value);
+            *entry.get_mut() = LogVar::ReadWrite(value, updated);
+        }
+
+        Ok(entry.into_mut())
+    }
+
     /// Combine two logs into a single log, to allow waiting for all reads.
+    #[allow(clippy::mutable_key_type)]
     fn combine(&mut self, vars: RegisterType) {
         // combine reads
         for (var, value) in vars {
@@ -901,7 +1190,15 @@ impl Transaction {
     /// This should be used before redoing a computation, but
     /// nowhere else.
     fn clear(&mut self) {
+        #[cfg(feature = "watchdog")]
+        self.watch.failed(self.name, self.vars.keys());
         self.vars.clear();
+        #[cfg(feature = "early-conflict-detection")]
+        {
+            self.early_conflict = false;
+        }
+        #[cfg(feature = "event-log")]
+        self.attempt.next();
     }
 
     /// Wait for any variable to change,
@@ -913,20 +1210,21 @@ impl Transaction {
 
         #[allow(clippy::mutable_key_type)]
         let vars = std::mem::take(&mut self.vars);
-        let mut reads = Vec::with_capacity(self.vars.len());
+        let mut reads = Vec::with_capacity(vars.len());
+        // The log is consumed here, keep its variables for the report of the failed attempt.
+        #[cfg(feature = "watchdog")]
+        self.watch.retried(vars.keys());
 
         let blocking = vars
             .into_iter()
             .filter_map(|(a, b)| b.into_read_value().map(|b| (a, b)))
             // Check for consistency.
             .all(|(var, value)| {
-                #[cfg(feature = "hash-registers")]
-                let var = unsafe { var.as_ref() }.expect("E: unreachabel");
-                var.wait(&ctrl);
+                var.waiters().wait(&ctrl);
                 let x = {
                     // Take read lock and read value.
-                    let guard = var.value.read();
-                    Arc::ptr_eq(&value, &guard)
+                    let guard = var.cell().lock_read();
+                    guard.is(&value)
                 };
                 reads.push(var);
                 x
@@ -934,8 +1232,28 @@ impl Transaction {
 
         // If no var has changed, then block.
         if blocking {
+            let _parking = Parking::new(self.name, &reads);
+            #[cfg(feature = "profiling")]
+            STATS.waits.incr();
+            #[cfg(any(feature = "profiling", feature = "tracing"))]
+            let start = std::time::Instant::now();
+
             // Propably wait until one var has changed.
+            #[cfg(feature = "watchdog")]
+            self.watch.wait(self.name, &ctrl, &reads);
+            #[cfg(not(feature = "watchdog"))]
             ctrl.wait();
+
+            #[cfg(any(feature = "profiling", feature = "tracing"))]
+            let parked = start.elapsed();
+            #[cfg(feature = "profiling")]
+            self.recorder.parked(parked);
+            #[cfg(feature = "tracing")]
+            tracing::debug!(?parked, "retry");
+        }
+        #[cfg(feature = "tracing")]
+        if !blocking {
+            tracing::debug!("retry without parking, a read variable already changed");
         }
 
         // Let others know that ctrl is dead.
@@ -943,7 +1261,7 @@ impl Transaction {
         // to dead since it may slightly reduce performance
         // but not break the semantics.
         for var in &reads {
-            var.set_dead();
+            var.waiters().set_dead();
         }
     }
 
@@ -967,25 +1285,37 @@ impl Transaction {
         // vector of written variables
         let mut written = Vec::with_capacity(self.vars.len());
 
+        // variable that failed validation, with the reason
+        let mut conflict = None;
+
         #[cfg(feature = "hash-registers")]
         let records = {
             let mut recs: Vec<_> = self.vars.iter().collect();
-            recs.sort_by(|(k1, _), (k2, _)| k1.cmp(&k2));
+            recs.sort_by_key(|(k, _)| *k);
             recs
         };
         #[cfg(not(feature = "hash-registers"))]
+        #[allow(clippy::mutable_key_type)]
         let records = &self.vars;
 
         for (var, value) in records {
             // lock the variable and read the value
-            #[cfg(feature = "hash-registers")]
-            let var = unsafe { var.as_ref() }.expect("E: unreachabel");
-
             match *value {
                 // We need to take a write lock.
                 LogVar::Write(ref w) | LogVar::ReadObsoleteWrite(_, ref w) => {
                     // take write lock
-                    let lock = var.value.write();
+                    let lock = var.cell().lock_write();
+                    // add all data to the vector
+                    write_vec.push((w.clone(), lock));
+                    written.push(var);
+                }
+
+                // We need to take a write lock, and compute the value
+                // from the current one.
+                LogVar::Commute(ref fs) => {
+                    // take write lock
+                    let lock = var.cell().lock_write();
+                    let w = log_var::apply(fs, &lock.get());
                     // add all data to the vector
                     write_vec.push((w, lock));
                     written.push(var);
@@ -995,13 +1325,14 @@ impl Transaction {
                 // take a write lock.
                 LogVar::ReadWrite(ref original, ref w) => {
                     // take write lock
-                    let lock = var.value.write();
+                    let lock = var.cell().lock_write();
 
-                    if !Arc::ptr_eq(&lock, original) {
-                        return false;
+                    if !lock.is(original) {
+                        conflict = Some((var, "updated value changed"));
+                        break;
                     }
                     // add all data to the vector
-                    write_vec.push((w, lock));
+                    write_vec.push((w.clone(), lock));
                     written.push(var);
                 }
                 // Nothing to do. ReadObsolete is only needed for blocking, not
@@ -1010,10 +1341,11 @@ impl Transaction {
                 // Take read lock and check for consistency.
                 LogVar::Read(ref original) => {
                     // Take a read lock.
-                    let lock = var.value.read();
+                    let lock = var.cell().lock_read();
 
-                    if !Arc::ptr_eq(&lock, original) {
-                        return false;
+                    if !lock.is(original) {
+                        conflict = Some((var, "read value changed"));
+                        break;
                     }
 
                     read_vec.push(lock);
@@ -1021,21 +1353,65 @@ impl Transaction {
             }
         }
 
+        if let Some((var, reason)) = conflict {
+            // Release the locks before recording the failure.
+            drop(read_vec);
+            drop(write_vec);
+            self.record_commit_failure(var, reason);
+            return false;
+        }
+
         // Second phase: write back and release
 
+        // Number the commit while all the variables are locked, so that the numbers follow the
+        // order of the commits. The attempt is logged once the variables are released.
+        #[cfg(feature = "event-log")]
+        let logged = event_log::recording().then(|| {
+            let written: Vec<_> = written
+                .iter()
+                .zip(&write_vec)
+                .map(|(var, (value, _))| (*var, value.clone()))
+                .collect();
+            (event_log::next_commit(), written)
+        });
+
         // Release the reads first.
         // This allows other threads to continue quickly.
         drop(read_vec);
 
         for (value, mut lock) in write_vec {
             // Commit value.
-            *lock = value.clone();
+            lock.set(value);
         }
 
+        #[cfg(any(feature = "profiling", feature = "tracing"))]
+        let n_written = written.len();
+
         #[cfg(feature = "wait-on-retry")]
         for var in written {
             // Unblock all threads waiting for it.
-            var.wake_all();
+            var.waiters().wake_all();
+        }
+
+        #[cfg(feature = "event-log")]
+        if let Some((seq, written)) = logged {
+            self.log_attempt(&written, Outcome::Commit(seq));
+        }
+
+        #[cfg(any(feature = "profiling", feature = "tracing"))]
+        {
+            let reads = self
+                .vars
+                .values()
+                .filter(|log| matches!(log, LogVar::Read(_) | LogVar::ReadWrite(..)))
+                .count();
+            #[cfg(feature = "profiling")]
+            {
+                STATS.commits.incr();
+                self.recorder.commit(reads, n_written);
+            }
+            #[cfg(feature = "tracing")]
+            tracing::debug!(reads, writes = n_written, "transaction committed");
         }
 
         // Commit succeded.
@@ -1069,6 +1445,135 @@ mod test {
         assert_eq!(var.read_atomic(), [1, 2]);
     }
 
+    /// Variables of different types, and slots of an array, are committed through their
+    /// type-erased storage.
+    #[test]
+    fn commit_mixed_types() {
+        let int = TVar::new(1);
+        let string = TVar::new(String::from("a"));
+        let array = crate::TArray::new([0u8; 2]);
+
+        let mut log = Transaction::default();
+        int.modify(&mut log, |x| x + 1).unwrap();
+        string.commute(&mut log, |s| s + "b").unwrap();
+        array.write(&mut log, 1, 42).unwrap();
+        assert_eq!(array.read(&mut log, 0).unwrap(), 0);
+        assert!(log.commit());
+
+        assert_eq!(int.read_atomic(), 2);
+        assert_eq!(string.read_atomic(), "ab");
+        assert_eq!(array.read_atomic(0), 0);
+        assert_eq!(array.read_atomic(1), 42);
+    }
+
+    /// Failed validations are attributed to the variable that changed.
+    #[cfg(feature = "profiling")]
+    #[test]
+    fn conflict_attribution() {
+        let hot = TVar::with_name(0, "hot");
+        let cold = TVar::new(0);
+
+        let tallies: TransactionTallies = (0..2)
+            .map(|_| {
+                let mut log = Transaction::default();
+                hot.read(&mut log).unwrap();
+                cold.read(&mut log).unwrap();
+                hot.write_atomic(hot.read_atomic() + 1);
+                assert!(!log.commit());
+                log.tallies
+            })
+            .sum();
+
+        let conflicts = tallies.hottest_conflicts(10);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].0.name(), Some("hot"));
+        assert_eq!(conflicts[0].1, 2);
+    }
+
+    /// Subscriber recording the names of spans and the messages of events.
+    #[cfg(feature = "tracing")]
+    #[derive(Default, Clone)]
+    struct Collector(Arc<parking_lot::Mutex<Vec<String>>>);
+
+    #[cfg(feature = "tracing")]
+    impl tracing::Subscriber for Collector {
+        fn enabled(&self, _: &tracing::Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+            let mut log = self.0.lock();
+            log.push(span.metadata().name().to_string());
+            tracing::span::Id::from_u64(log.len() as u64)
+        }
+
+        fn record(&self, _: &tracing::span::Id, _: &tracing::span::Record<'_>) {}
+
+        fn record_follows_from(&self, _: &tracing::span::Id, _: &tracing::span::Id) {}
+
+        fn event(&self, event: &tracing::Event<'_>) {
+            struct Message<'a>(&'a mut Vec<String>);
+
+            impl tracing::field::Visit for Message<'_> {
+                fn record_debug(
+                    &mut self,
+                    field: &tracing::field::Field,
+                    value: &dyn std::fmt::Debug,
+                ) {
+                    if field.name() == "message" {
+                        self.0.push(format!("{value:?}"));
+                    }
+                }
+            }
+
+            event.record(&mut Message(&mut self.0.lock()));
+        }
+
+        fn enter(&self, _: &tracing::span::Id) {}
+
+        fn exit(&self, _: &tracing::span::Id) {}
+    }
+
+    /// Attempts are traced in spans, with events for conflicts, `or` branches and aborts.
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn traced_events() {
+        let var = TVar::new(0);
+        let collector = Collector::default();
+
+        tracing::subscriber::with_default(collector.clone(), || {
+            Transaction::with_name("traced", |trans| {
+                let x = var.read(trans)?;
+                if x == 0 {
+                    // Make the first attempt fail.
+                    var.write_atomic(1);
+                }
+                trans.or(|_| crate::retry(), |trans| var.write(trans, x + 1))
+            });
+            let aborted: Option<()> =
+                Transaction::with_control(|_| TransactionControl::Abort, |_| crate::retry());
+            assert!(aborted.is_none());
+        });
+
+        assert_eq!(var.read_atomic(), 2);
+        assert_eq!(
+            *collector.0.lock(),
+            [
+                "atomically",
+                "attempt",
+                "first branch retried, running the second one",
+                "commit failed",
+                "attempt",
+                "first branch retried, running the second one",
+                "transaction committed",
+                "atomically",
+                "attempt",
+                "attempt failed",
+                "transaction aborted",
+            ]
+        );
+    }
+
     #[test]
     fn transaction_simple() {
         let x = Transaction::with(|_| Ok(42));
@@ -1106,6 +1611,45 @@ mod test {
         assert_eq!(x, None);
     }
 
+    /// The control function is told why each attempt failed.
+    #[test]
+    fn transaction_with_control_reasons() {
+        let var = TVar::new(0);
+        let attempt = Cell::new(0);
+        let mut reasons = Vec::new();
+
+        let x = Transaction::with_control(
+            |reason| {
+                reasons.push(reason);
+                TransactionControl::Retry
+            },
+            |trans| {
+                attempt.set(attempt.get() + 1);
+                let x = var.read(trans)?;
+                match attempt.get() {
+                    1 => var.write_atomic(x + 1),
+                    2 => return Err(StmError::Failure),
+                    #[cfg(feature = "early-conflict-detection")]
+                    3 => {
+                        var.write_atomic(x + 1);
+                        var.read(trans)?;
+                    }
+                    _ => {}
+                }
+                Ok(x)
+            },
+        );
+
+        let expected = [
+            FailureReason::CommitConflict,
+            FailureReason::Failure,
+            #[cfg(feature = "early-conflict-detection")]
+            FailureReason::EarlyConflict,
+        ];
+        assert_eq!(reasons, expected);
+        assert_eq!(Some(var.read_atomic()), x);
+    }
+
     #[test]
     fn transaction_write() {
         let write = TVar::new(42);
@@ -1142,6 +1686,48 @@ mod test {
         assert_eq!(write.read_atomic(), 42);
     }
 
+    /// Concurrent commutative updates should not conflict.
+    #[test]
+    fn commute_no_conflict() {
+        let var = TVar::new(0);
+
+        let mut tx1 = Transaction::default();
+        let mut tx2 = Transaction::default();
+        tx1.commute(&var, |x| x + 1).unwrap();
+        tx1.commute(&var, |x| x * 10).unwrap();
+        tx2.write(&var, 4).unwrap();
+
+        assert!(tx2.commit());
+        assert!(tx1.commit());
+        assert_eq!(var.read_atomic(), 50);
+    }
+
+    /// Reading a commuted variable turns the update into a regular modification.
+    #[test]
+    fn commute_read() {
+        let var = TVar::new(1);
+
+        let mut tx1 = Transaction::default();
+        let mut tx2 = Transaction::default();
+        tx1.commute(&var, |x| x + 1).unwrap();
+        assert_eq!(tx1.read(&var).unwrap(), 2);
+        tx2.write(&var, 4).unwrap();
+
+        assert!(tx2.commit());
+        assert!(!tx1.commit());
+        assert_eq!(var.read_atomic(), 4);
+    }
+
+    /// Nested transactions are reported with their names.
+    #[test]
+    #[should_panic(expected = "STM: Nested Transaction `inner` inside `outer`")]
+    fn transaction_nested_named() {
+        Transaction::with_name("outer", |_| {
+            Transaction::with_name("inner", |_| Ok(42));
+            Ok(1)
+        });
+    }
+
     /// Test if nested transactions are correctly detected.
     #[test]
     #[should_panic]
@@ -8,11 +8,15 @@
 
 #[cfg(feature = "wait-on-retry")]
 use parking_lot::Mutex;
-use parking_lot::RwLock;
+use parking_lot::{
+    MappedRwLockReadGuard, MappedRwLockWriteGuard, RwLock, RwLockReadGuard, RwLockWriteGuard,
+};
 use std::any::Any;
 use std::cmp;
 use std::fmt::{self, Debug};
-use std::marker::PhantomData;
+use std::hash::{Hash, Hasher};
+#[cfg(feature = "wait-on-retry")]
+use std::collections::HashSet;
 #[cfg(feature = "wait-on-retry")]
 use std::sync::atomic::{self, AtomicUsize};
 use std::sync::Arc;
@@ -22,15 +26,14 @@ use std::sync::Weak;
 use super::result::StmClosureResult;
 #[cfg(feature = "wait-on-retry")]
 use super::transaction::control_block::ControlBlock;
+use super::transaction::log_var::{downcast_arc, ArcAny};
 use super::Transaction;
 
-/// `VarControlBlock` contains all the useful data for a `Var` while beeing the same type.
-///
-/// The control block is accessed from other threads directly whereas `Var`
-/// is just a typesafe wrapper around it.
-pub struct VarControlBlock {
+/// `WaitQueue` keeps track of the threads waiting for a change of one or more variables.
+#[cfg(feature = "wait-on-retry")]
+#[derive(Default)]
+pub struct WaitQueue {
     /// `waiting_threads` is a list of all waiting threads protected by a mutex.
-    #[cfg(feature = "wait-on-retry")]
     waiting_threads: Mutex<Vec<Weak<ControlBlock>>>,
 
     /// `dead_threads` is a counter for all dead threads.
@@ -38,56 +41,12 @@ pub struct VarControlBlock {
     /// When there are many dead threads waiting for a change, but
     /// nobody changes the value, then an automatic collection is
     /// performed.
-    #[cfg(feature = "wait-on-retry")]
     dead_threads: AtomicUsize,
-
-    /// The inner value of the Var.
-    ///
-    /// It can be shared through a Arc without copying it too often.
-    ///
-    /// The Arc is also used by the threads to detect changes.
-    /// The value in it should not be changed or locked because
-    /// that may cause multiple threads to block unforeseen as well as
-    /// causing deadlocks.
-    ///
-    /// The shared reference is protected by a `RWLock` so that multiple
-    /// threads can safely block it. This ensures consistency, without
-    /// preventing other threads from accessing the values.
-    ///
-    /// Starvation may occur, if one thread wants to write-lock but others
-    /// keep holding read-locks.
-    pub value: RwLock<Arc<dyn Any + Send + Sync>>,
 }
 
-impl VarControlBlock {
-    #[cfg(feature = "wait-on-retry")]
-    /// create a new empty `VarControlBlock`
-    pub fn new<T>(val: T) -> Arc<VarControlBlock>
-    where
-        T: Any + Sync + Send,
-    {
-        let ctrl = VarControlBlock {
-            waiting_threads: Mutex::new(Vec::new()),
-            dead_threads: AtomicUsize::new(0),
-            value: RwLock::new(Arc::new(val)),
-        };
-        Arc::new(ctrl)
-    }
-
-    #[cfg(not(feature = "wait-on-retry"))]
-    /// create a new empty `VarControlBlock`
-    pub fn new<T>(val: T) -> Arc<VarControlBlock>
-    where
-        T: Any + Sync + Send,
-    {
-        let ctrl = VarControlBlock {
-            value: RwLock::new(Arc::new(val)),
-        };
-        Arc::new(ctrl)
-    }
-
-    #[cfg(feature = "wait-on-retry")]
-    /// Wake all threads that are waiting for this block.
+#[cfg(feature = "wait-on-retry")]
+impl WaitQueue {
+    /// Wake all threads that are waiting in this queue.
     pub fn wake_all(&self) {
         // Atomically take all waiting threads from the value.
         let threads = {
@@ -100,21 +59,55 @@ impl VarControlBlock {
         let threads = threads.iter().filter_map(Weak::upgrade);
 
         // Release all the semaphores to start the thread.
+        #[cfg(feature = "tracing")]
+        let mut woken = 0;
         for thread in threads {
             // Inform thread that this var has changed.
             thread.set_changed();
+            #[cfg(feature = "profiling")]
+            crate::stats::STATS.wakeups.incr();
+            #[cfg(feature = "tracing")]
+            {
+                woken += 1;
+            }
+        }
+
+        #[cfg(feature = "tracing")]
+        if woken > 0 {
+            tracing::trace!(woken, "woke up waiting transactions");
         }
     }
 
-    #[cfg(feature = "wait-on-retry")]
-    /// Add another thread, that waits for mutations of `self`.
+    /// Return the number of threads currently waiting in this queue.
+    ///
+    /// A thread waiting for several variables sharing the queue, e.g. slots of a `TArray`, has
+    /// one entry per variable, but is counted once.
+    pub fn waiting(&self) -> usize {
+        self.waiting_threads
+            .lock()
+            .iter()
+            .filter(|t| t.strong_count() > 0)
+            .map(Weak::as_ptr)
+            .collect::<HashSet<_>>()
+            .len()
+    }
+
+    /// Return the number of entries left in this queue by threads that stopped waiting.
+    pub fn dead(&self) -> usize {
+        self.waiting_threads
+            .lock()
+            .iter()
+            .filter(|t| t.strong_count() == 0)
+            .count()
+    }
+
+    /// Add another thread, that waits for mutations of the variables.
     pub fn wait(&self, thread: &Arc<ControlBlock>) {
         let mut guard = self.waiting_threads.lock();
 
         guard.push(Arc::downgrade(thread));
     }
 
-    #[cfg(feature = "wait-on-retry")]
     /// Mark another `StmControlBlock` as dead.
     ///
     /// If the count of dead control blocks is too high,
@@ -136,89 +129,333 @@ impl VarControlBlock {
 
             // Remove all dead ones. Possibly free up the memory.
             guard.retain(|t| t.upgrade().is_some());
+            #[cfg(feature = "profiling")]
+            crate::stats::STATS.dead_waiter_collections.incr();
         }
     }
+}
 
-    fn get_address(&self) -> usize {
-        std::ptr::from_ref::<VarControlBlock>(self) as usize
+/// `VarControlBlock` contains all the useful data for a `Var`.
+///
+/// The control block is accessed from other threads directly whereas `Var`
+/// is just a wrapper around it. Transactions access it with its type when reading and
+/// writing the log, and through the type-erased `Storage` trait when committing.
+pub struct VarControlBlock<T> {
+    /// Threads waiting for a change of the var.
+    #[cfg(feature = "wait-on-retry")]
+    pub waiters: WaitQueue,
+
+    /// The inner value of the Var.
+    ///
+    /// It can be shared through a Arc without copying it too often.
+    ///
+    /// The Arc is also used by the threads to detect changes.
+    /// The value in it should not be changed or locked because
+    /// that may cause multiple threads to block unforeseen as well as
+    /// causing deadlocks.
+    ///
+    /// The shared reference is protected by a `RWLock` so that multiple
+    /// threads can safely block it. This ensures consistency, without
+    /// preventing other threads from accessing the values.
+    ///
+    /// Starvation may occur, if one thread wants to write-lock but others
+    /// keep holding read-locks.
+    pub value: RwLock<Arc<T>>,
+
+    /// Optional label of the var, used for diagnostics.
+    pub name: Option<&'static str>,
+}
+
+impl<T> VarControlBlock<T>
+where
+    T: Any + Sync + Send,
+{
+    /// create a new empty `VarControlBlock`
+    pub fn new(val: T, name: Option<&'static str>) -> Arc<VarControlBlock<T>> {
+        let ctrl = VarControlBlock {
+            #[cfg(feature = "wait-on-retry")]
+            waiters: WaitQueue::default(),
+            value: RwLock::new(Arc::new(val)),
+            name,
+        };
+        Arc::new(ctrl)
     }
 }
 
-// Implement some operators so that VarControlBlocks can be sorted.
+/// Type-erased access to the value of a variable, locked by a transaction.
+pub trait LockedValue {
+    /// Return the current value.
+    fn get(&self) -> ArcAny;
 
-impl PartialEq for VarControlBlock {
+    /// Check if the current value is `value`.
+    fn is(&self, value: &ArcAny) -> bool;
+
+    /// Replace the current value with a value taken from a transaction log.
+    fn set(&mut self, value: ArcAny);
+}
+
+impl<T> LockedValue for Arc<T>
+where
+    T: Any + Sync + Send,
+{
+    fn get(&self) -> ArcAny {
+        self.clone()
+    }
+
+    fn is(&self, value: &ArcAny) -> bool {
+        same_value(value, self)
+    }
+
+    fn set(&mut self, value: ArcAny) {
+        *self = downcast_arc(value);
+    }
+}
+
+/// Type-erased access to the lock protecting the value of a variable.
+pub trait Cell: Send + Sync {
+    /// Take a read lock on the value.
+    fn lock_read(&self) -> MappedRwLockReadGuard<'_, dyn LockedValue>;
+
+    /// Take a write lock on the value.
+    fn lock_write(&self) -> MappedRwLockWriteGuard<'_, dyn LockedValue>;
+}
+
+impl<T> Cell for RwLock<Arc<T>>
+where
+    T: Any + Sync + Send,
+{
+    fn lock_read(&self) -> MappedRwLockReadGuard<'_, dyn LockedValue> {
+        RwLockReadGuard::map(self.read(), |value| value as &dyn LockedValue)
+    }
+
+    fn lock_write(&self) -> MappedRwLockWriteGuard<'_, dyn LockedValue> {
+        RwLockWriteGuard::map(self.write(), |value| value as &mut dyn LockedValue)
+    }
+}
+
+/// Type-erased access to a control block, holding one or more variables.
+pub trait Storage: Send + Sync {
+    /// Access the variable at `idx`.
+    fn cell(&self, idx: usize) -> &dyn Cell;
+
+    /// Return the label of the variable at `idx`, if any.
+    #[cfg(diagnostics)]
+    fn name(&self, idx: usize) -> Option<&'static str>;
+
+    /// Access the queue of threads waiting for a change of the variables.
+    #[cfg(feature = "wait-on-retry")]
+    fn waiters(&self) -> &WaitQueue;
+}
+
+impl<T> Storage for VarControlBlock<T>
+where
+    T: Any + Sync + Send,
+{
+    fn cell(&self, _idx: usize) -> &dyn Cell {
+        &self.value
+    }
+
+    #[cfg(diagnostics)]
+    fn name(&self, _idx: usize) -> Option<&'static str> {
+        self.name
+    }
+
+    #[cfg(feature = "wait-on-retry")]
+    fn waiters(&self) -> &WaitQueue {
+        &self.waiters
+    }
+}
+
+/// Check if a value taken from a transaction log is the same as the value of a variable.
+pub(crate) fn same_value<T>(logged: &ArcAny, value: &Arc<T>) -> bool {
+    std::ptr::addr_eq(Arc::as_ptr(logged), Arc::as_ptr(value))
+}
+
+/// Identifier of a variable in profiling reports and traces.
+///
+/// It holds the label of the variable, if any, and the address of its storage. The address is
+/// only unique among the variables alive at the same time.
+#[cfg(diagnostics)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct VarId {
+    name: Option<&'static str>,
+    address: usize,
+}
+
+#[cfg(diagnostics)]
+impl VarId {
+    /// Return the label of the variable, if any.
+    pub fn name(&self) -> Option<&'static str> {
+        self.name
+    }
+
+    /// Return the address of the storage of the variable.
+    pub fn address(&self) -> usize {
+        self.address
+    }
+}
+
+#[cfg(diagnostics)]
+impl fmt::Display for VarId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self.name {
+            Some(name) => write!(f, "{name}@{:#x}", self.address),
+            None => write!(f, "{:#x}", self.address),
+        }
+    }
+}
+
+/// `VarRef` is an owning handle to the storage of a single variable.
+///
+/// It is used as the key of transaction registers. It is compared using the address of the
+/// control block and the index of the variable in it.
+#[derive(Clone)]
+pub struct VarRef {
+    storage: Arc<dyn Storage>,
+    idx: usize,
+}
+
+impl VarRef {
+    /// Create a handle to the variable at `idx` in `storage`.
+    pub(crate) fn new(storage: Arc<dyn Storage>, idx: usize) -> Self {
+        Self { storage, idx }
+    }
+
+    /// Access the value of the variable.
+    pub fn cell(&self) -> &dyn Cell {
+        self.storage.cell(self.idx)
+    }
+
+    /// Access the queue of threads waiting for a change of the variable.
+    #[cfg(feature = "wait-on-retry")]
+    pub fn waiters(&self) -> &WaitQueue {
+        self.storage.waiters()
+    }
+
+    /// Identify the variable in profiling reports and traces.
+    #[cfg(diagnostics)]
+    pub fn id(&self) -> VarId {
+        VarId {
+            name: self.storage.name(self.idx),
+            address: std::ptr::from_ref(self.cell()).cast::<()>() as usize,
+        }
+    }
+
+    /// Check if this handle is the only remaining reference to the variable, so that nothing
+    /// can write it anymore.
+    #[cfg(all(feature = "watchdog", feature = "wait-on-retry"))]
+    pub(crate) fn is_orphaned(&self) -> bool {
+        Arc::strong_count(&self.storage) == 1
+    }
+
+    fn get_address(&self) -> (usize, usize) {
+        (Arc::as_ptr(&self.storage).cast::<()>() as usize, self.idx)
+    }
+}
+
+// Implement some operators so that VarRefs can be sorted and hashed.
+
+impl PartialEq for VarRef {
     fn eq(&self, other: &Self) -> bool {
         self.get_address() == other.get_address()
     }
 }
 
-impl Eq for VarControlBlock {}
+impl Eq for VarRef {}
 
-impl Ord for VarControlBlock {
+impl Ord for VarRef {
     fn cmp(&self, other: &Self) -> cmp::Ordering {
         self.get_address().cmp(&other.get_address())
     }
 }
 
-impl PartialOrd for VarControlBlock {
+impl PartialOrd for VarRef {
     fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
         Some(self.cmp(other))
     }
 }
 
+impl Hash for VarRef {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.get_address().hash(state);
+    }
+}
+
 /// A variable that can be used in a STM-Block
-#[derive(Clone)]
+///
+/// Values are stored behind an `Arc`. Types that do not implement `Clone` can be stored as
+/// well, and accessed with `read_arc`, `write` and `modify_with`; only the convenience methods
+/// returning owned values require `Clone`.
 pub struct TVar<T> {
     /// The control block is the inner of the variable.
     ///
     /// The rest of `TVar` is just the typesafe interface.
-    control_block: Arc<VarControlBlock>,
-
-    /// This marker is needed so that the variable can be used in a typesafe
-    /// manner.
-    _marker: PhantomData<T>,
+    control_block: Arc<VarControlBlock<T>>,
 }
 
 impl<T> TVar<T>
 where
-    T: Any + Sync + Send + Clone,
+    T: Any + Sync + Send,
 {
     /// Create a new `TVar`.
     pub fn new(val: T) -> TVar<T> {
         TVar {
-            control_block: VarControlBlock::new(val),
-            _marker: PhantomData,
+            control_block: VarControlBlock::new(val, None),
         }
     }
 
-    #[allow(clippy::missing_panics_doc)]
-    /// `read_atomic` reads a value atomically, without starting a transaction.
+    /// Create a new `TVar` with a label.
     ///
-    /// It is semantically equivalent to
+    /// The label identifies the var in diagnostics, such as the conflicts recorded by the
+    /// `profiling` feature.
+    pub fn with_name(val: T, name: &'static str) -> TVar<T> {
+        TVar {
+            control_block: VarControlBlock::new(val, Some(name)),
+        }
+    }
+
+    /// Label the var, like `with_name`.
+    ///
+    /// This is meant to be chained after the creation of the var:
     ///
     /// ```
     /// # use fast_stm::*;
+    /// let balance = TVar::new(0).named("balance");
     ///
-    /// let var = TVar::new(0);
-    /// atomically(|trans| var.read(trans));
+    /// assert_eq!(balance.name(), Some("balance"));
     /// ```
     ///
-    /// but more efficient.
+    /// # Panics
     ///
-    /// `read_atomic` returns a clone of the value.
-    ///
-    /// <div class="warning">
-    ///
-    /// This method should not be used inside transactions.
-    ///
-    /// </div>
-    pub fn read_atomic(&self) -> T {
-        let val = self.read_ref_atomic();
+    /// Panics if the var has been cloned, since other handles would not see the label.
+    #[must_use]
+    pub fn named(mut self, name: &'static str) -> TVar<T> {
+        Arc::get_mut(&mut self.control_block)
+            .expect("STM: cannot name a TVar that has been cloned")
+            .name = Some(name);
+        self
+    }
+
+    /// Return the label of the var, if any.
+    pub fn name(&self) -> Option<&'static str> {
+        self.control_block.name
+    }
 
-        (&*val as &dyn Any)
-            .downcast_ref::<T>()
-            .expect("wrong type in Var<T>")
-            .clone()
+    /// Return the number of transactions parked on `retry` until the var changes.
+    #[cfg(feature = "wait-on-retry")]
+    pub fn waiter_count(&self) -> usize {
+        self.control_block.waiters.waiting()
+    }
+
+    /// Return the number of stale entries in the wait queue of the var.
+    ///
+    /// An entry becomes stale when its transaction stops waiting because another variable
+    /// changed. Stale entries are dropped when the var is written, or when enough transactions
+    /// stopped waiting for it; a growing count means the var is often waited for, but rarely
+    /// written.
+    #[cfg(feature = "wait-on-retry")]
+    pub fn dead_waiter_count(&self) -> usize {
+        self.control_block.waiters.dead()
     }
 
     #[allow(clippy::missing_panics_doc)]
@@ -241,9 +478,21 @@ where
     ///
     /// </div>
     pub fn write_atomic(&self, value: T) {
-        let mut val = self.control_block.value.write();
-        let boxed = Arc::new(value);
-        *val = boxed;
+        *self.control_block.value.write() = Arc::new(value);
+    }
+
+    /// `write_arc_atomic` writes a value atomically, without starting a transaction.
+    ///
+    /// Like `write_arc`, the `Arc` is stored as is, so other holders of it keep seeing
+    /// the same value.
+    ///
+    /// <div class="warning">
+    ///
+    /// This method should not be used inside transactions.
+    ///
+    /// </div>
+    pub fn write_arc_atomic(&self, value: Arc<T>) {
+        *self.control_block.value.write() = value;
     }
 
     /// Read a value atomically but return a reference.
@@ -255,12 +504,34 @@ where
         self.control_block.value.read().clone()
     }
 
-    /// The normal way to access a var.
+    /// `read_arc_atomic` reads a value atomically, without starting a transaction.
     ///
-    /// It is equivalent to `transaction.read(&var)`, but more
+    /// Unlike `read_atomic`, it does not clone the value, but returns a shared reference to it.
+    ///
+    /// <div class="warning">
+    ///
+    /// This method should not be used inside transactions.
+    ///
+    /// </div>
+    pub fn read_arc_atomic(&self) -> Arc<T> {
+        self.control_block.value.read().clone()
+    }
+
+    /// Read a var without cloning its value.
+    ///
+    /// It is equivalent to `transaction.read_arc(&var)`, but more
     /// convenient.
-    pub fn read(&self, transaction: &mut Transaction) -> StmClosureResult<T> {
-        transaction.read(self)
+    ///
+    /// ```
+    /// # use fast_stm::*;
+    /// // `Vec<u8>` is cloneable, but reading it with `read` would copy the whole buffer.
+    /// let var = TVar::new(vec![0u8; 1 << 20]);
+    /// let len = atomically(|trans| Ok(var.read_arc(trans)?.len()));
+    ///
+    /// assert_eq!(len, 1 << 20);
+    /// ```
+    pub fn read_arc(&self, transaction: &mut Transaction) -> StmClosureResult<Arc<T>> {
+        transaction.read_arc(self)
     }
 
     /// The normal way to write a var.
@@ -271,6 +542,91 @@ where
         transaction.write(self, value)
     }
 
+    /// Write a value that is already stored in an `Arc`.
+    ///
+    /// It is equivalent to `transaction.write_arc(&var, value)`, but more
+    /// convenient.
+    pub fn write_arc(&self, transaction: &mut Transaction, value: Arc<T>) -> StmClosureResult<()> {
+        transaction.write_arc(self, value)
+    }
+
+    /// Modify the content of a `TVar` with the function f, which computes the new value from a
+    /// reference to the current one.
+    ///
+    /// Unlike `modify`, this does not require `T: Clone`.
+    ///
+    /// ```
+    /// # use fast_stm::*;
+    /// struct Id(u64);
+    ///
+    /// let var = TVar::new(Id(1));
+    /// atomically(|trans|
+    ///     var.modify_with(trans, |id| Id(id.0 + 1))
+    /// );
+    ///
+    /// assert_eq!(var.read_arc_atomic().0, 2);
+    /// ```
+    pub fn modify_with<F>(&self, transaction: &mut Transaction, f: F) -> StmClosureResult<()>
+    where
+        F: FnOnce(&T) -> T,
+    {
+        transaction.modify_with(self, f)
+    }
+
+    /// Check if two `TVar`s refer to the same position.
+    pub fn ref_eq(this: &TVar<T>, other: &TVar<T>) -> bool {
+        Arc::ptr_eq(&this.control_block, &other.control_block)
+    }
+
+    /// Access the control block of the var.
+    ///
+    /// Internal use only!
+    pub fn control_block(&self) -> &Arc<VarControlBlock<T>> {
+        &self.control_block
+    }
+
+    /// Create a handle to the var, used as a key in transaction logs.
+    pub(crate) fn var_ref(&self) -> VarRef {
+        VarRef::new(self.control_block.clone(), 0)
+    }
+}
+
+impl<T> TVar<T>
+where
+    T: Any + Sync + Send + Clone,
+{
+    /// `read_atomic` reads a value atomically, without starting a transaction.
+    ///
+    /// It is semantically equivalent to
+    ///
+    /// ```
+    /// # use fast_stm::*;
+    ///
+    /// let var = TVar::new(0);
+    /// atomically(|trans| var.read(trans));
+    /// ```
+    ///
+    /// but more efficient.
+    ///
+    /// `read_atomic` returns a clone of the value.
+    ///
+    /// <div class="warning">
+    ///
+    /// This method should not be used inside transactions.
+    ///
+    /// </div>
+    pub fn read_atomic(&self) -> T {
+        T::clone(&self.control_block.value.read())
+    }
+
+    /// The normal way to access a var.
+    ///
+    /// It is equivalent to `transaction.read(&var)`, but more
+    /// convenient.
+    pub fn read(&self, transaction: &mut Transaction) -> StmClosureResult<T> {
+        transaction.read(self)
+    }
+
     /// Modify the content of a `TVar` with the function f.
     ///
     /// Prefer this method over calling `read` then `write` for performance.
@@ -293,6 +649,28 @@ where
         transaction.modify(self, f)
     }
 
+    /// Update the content of a `TVar` with the commutative function f.
+    ///
+    /// The update is applied when committing, and does not conflict with other transactions.
+    /// See [`Transaction::commute`] for more details.
+    ///
+    /// ```
+    /// # use fast_stm::*;
+    ///
+    /// let var = TVar::new(21);
+    /// atomically(|trans|
+    ///     var.commute(trans, |x| x*2)
+    /// );
+    ///
+    /// assert_eq!(var.read_atomic(), 42);
+    /// ```
+    pub fn commute<F>(&self, transaction: &mut Transaction, f: F) -> StmClosureResult<()>
+    where
+        F: Fn(T) -> T + Send + Sync + 'static,
+    {
+        transaction.commute(self, f)
+    }
+
     /// Replaces the value of a `TVar` with a new one, returning the old one.
     ///
     /// Prefer this method over calling `read` then `write` for performance.
@@ -311,17 +689,14 @@ where
     pub fn exchange(&self, transaction: &mut Transaction, value: T) -> StmClosureResult<T> {
         transaction.exchange(self, value)
     }
+}
 
-    /// Check if two `TVar`s refer to the same position.
-    pub fn ref_eq(this: &TVar<T>, other: &TVar<T>) -> bool {
-        Arc::ptr_eq(&this.control_block, &other.control_block)
-    }
-
-    /// Access the control block of the var.
-    ///
-    /// Internal use only!
-    pub fn control_block(&self) -> &Arc<VarControlBlock> {
-        &self.control_block
+/// Cloning a `TVar` yields a new handle to the same variable; the value itself is not cloned.
+impl<T> Clone for TVar<T> {
+    fn clone(&self) -> Self {
+        TVar {
+            control_block: self.control_block.clone(),
+        }
     }
 }
 
@@ -336,13 +711,17 @@ where
 /// prints the state.
 impl<T> Debug for TVar<T>
 where
-    T: Any + Sync + Send + Clone,
+    T: Any + Sync + Send,
     T: Debug,
 {
     #[inline(never)]
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
-        let x = self.read_atomic();
-        f.debug_struct("TVar").field("value", &x).finish()
+        let x = self.read_arc_atomic();
+        let mut s = f.debug_struct("TVar");
+        if let Some(name) = self.name() {
+            s.field("name", &name);
+        }
+        s.field("value", &x).finish()
     }
 }
 
@@ -354,4 +733,55 @@ fn test_read_atomic() {
     assert_eq!(42, var.read_atomic());
 }
 
+#[test]
+// Test that `read_arc` works on values that are not `Clone`, and does not copy them.
+fn test_read_arc() {
+    use crate::atomically;
+
+    struct NoClone(u32);
+
+    let var = TVar::new(NoClone(42));
+    let a = atomically(|trans| var.read_arc(trans));
+    let b = var.read_arc_atomic();
+
+    assert_eq!(a.0, 42);
+    assert!(Arc::ptr_eq(&a, &b));
+
+    atomically(|trans| var.write(trans, NoClone(43)));
+    assert_eq!(var.read_arc_atomic().0, 43);
+    assert_eq!(a.0, 42);
+}
+
+#[test]
+// Test the `Arc`-based API on a type that is not `Clone`.
+fn test_non_clone() {
+    use crate::atomically;
+
+    #[derive(Debug)]
+    struct Handle(u64);
+
+    let var = TVar::new(Handle(1));
+    let var2 = var.clone();
+
+    let value = Arc::new(Handle(2));
+    atomically(|trans| var.write_arc(trans, value.clone()));
+    assert!(Arc::ptr_eq(&var2.read_arc_atomic(), &value));
+
+    atomically(|trans| var2.modify_with(trans, |h| Handle(h.0 * 21)));
+    assert_eq!(var.read_arc_atomic().0, 42);
+
+    var.write_arc_atomic(value);
+    assert_eq!(format!("{var2:?}"), "TVar { value: Handle(2) }");
+}
+
+#[test]
+// Test that labels show up in the debug output.
+fn test_named() {
+    let var = TVar::with_name(1, "one");
+    assert_eq!(format!("{var:?}"), r#"TVar { name: "one", value: 1 }"#);
+
+    let var = TVar::new(2).named("two");
+    assert_eq!(var.name(), Some("two"));
+}
+
 // More tests are in lib.rs.
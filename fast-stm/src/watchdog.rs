@@ -0,0 +1,337 @@
+//! Detection of transactions that keep failing, run for too long, or wait forever.
+//!
+//! Checks run on the thread of the transaction, after each failed attempt and while it is
+//! parked on `retry`. A single attempt that never ends, e.g. because of an infinite loop in the
+//! transaction body, is not detected.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use parking_lot::RwLock;
+
+#[cfg(feature = "wait-on-retry")]
+use crate::transaction::control_block::ControlBlock;
+use crate::tvar::{VarId, VarRef};
+
+/// Watchdog used by transactions starting from now on.
+static WATCHDOG: RwLock<Option<Arc<Watchdog>>> = RwLock::new(None);
+
+/// Kind of problem found by the watchdog.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchdogAlert {
+    /// The transaction failed more attempts than `Watchdog::max_attempts`.
+    TooManyAttempts,
+    /// The transaction has been running for longer than `Watchdog::max_duration`, including
+    /// the time spent parked.
+    TooLong,
+    /// The transaction is parked on `retry`, but no other handle to the variables it waits for
+    /// exists, so nothing can wake it up.
+    Orphaned,
+}
+
+/// Report of a problematic transaction, passed to the callback of the `Watchdog`.
+#[derive(Debug, Clone)]
+pub struct WatchdogReport {
+    /// Kind of problem found.
+    pub alert: WatchdogAlert,
+    /// Name of the transaction, if any.
+    pub name: Option<&'static str>,
+    /// Number of attempts run so far, including the current one.
+    pub attempts: u64,
+    /// Time since the start of the first attempt.
+    pub elapsed: Duration,
+    /// Variables accessed by the last attempt.
+    pub vars: Vec<VarId>,
+}
+
+/// A process-wide watchdog, flagging transactions that keep failing, run for too long, or wait
+/// for variables nothing can write anymore.
+///
+/// Each problem is reported once per transaction, by calling the callback on the thread running
+/// the transaction. The callback must not run transactions itself.
+///
+/// ```
+/// # use fast_stm::*;
+/// # use std::time::Duration;
+/// Watchdog::new(|report| eprintln!("STM watchdog: {report:?}"))
+///     .max_attempts(1000)
+///     .max_duration(Duration::from_secs(10))
+///     .install();
+///
+/// let var = TVar::new(0);
+/// atomically(|trans| var.modify(trans, |x| x + 1));
+///
+/// Watchdog::uninstall();
+/// ```
+pub struct Watchdog {
+    max_attempts: Option<u64>,
+    max_duration: Option<Duration>,
+    callback: Box<dyn Fn(&WatchdogReport) + Send + Sync>,
+}
+
+impl Watchdog {
+    /// Create a watchdog calling `callback` for each problem found.
+    ///
+    /// Without limits, only orphaned transactions are reported.
+    pub fn new<F>(callback: F) -> Self
+    where
+        F: Fn(&WatchdogReport) + Send + Sync + 'static,
+    {
+        Self {
+            max_attempts: None,
+            max_duration: None,
+            callback: Box::new(callback),
+        }
+    }
+
+    /// Report transactions that failed `n` attempts.
+    #[must_use]
+    pub fn max_attempts(mut self, n: u64) -> Self {
+        self.max_attempts = Some(n);
+        self
+    }
+
+    /// Report transactions running for longer than `duration`.
+    #[must_use]
+    pub fn max_duration(mut self, duration: Duration) -> Self {
+        self.max_duration = Some(duration);
+        self
+    }
+
+    /// Install the watchdog for the whole process, replacing the previous one.
+    ///
+    /// Transactions that are already running keep the watchdog they started with.
+    pub fn install(self) {
+        *WATCHDOG.write() = Some(Arc::new(self));
+    }
+
+    /// Remove the watchdog of the process.
+    pub fn uninstall() {
+        *WATCHDOG.write() = None;
+    }
+}
+
+/// Watchdog state of a running transaction.
+pub(crate) struct Watch {
+    watchdog: Option<Arc<Watchdog>>,
+    start: Instant,
+    /// Number of failed attempts.
+    n_failed: u64,
+    /// Alerts already reported for the transaction.
+    reported: Vec<WatchdogAlert>,
+    /// Variables accessed by the current attempt, if it called `retry` and its log was consumed.
+    retried: Option<Vec<VarId>>,
+}
+
+impl Default for Watch {
+    fn default() -> Self {
+        Self {
+            watchdog: WATCHDOG.read().clone(),
+            start: Instant::now(),
+            n_failed: 0,
+            reported: Vec::new(),
+            retried: None,
+        }
+    }
+}
+
+impl Watch {
+    /// Register a failed attempt of the transaction `name`, which accessed `vars`.
+    pub(crate) fn failed<'a, I>(&mut self, name: Option<&'static str>, vars: I)
+    where
+        I: IntoIterator<Item = &'a VarRef>,
+    {
+        self.n_failed += 1;
+        let retried = self.retried.take();
+        let Some(watchdog) = self.watchdog.clone() else {
+            return;
+        };
+        let vars = retried.unwrap_or_else(|| vars.into_iter().map(VarRef::id).collect());
+        let attempts = self.n_failed;
+        if watchdog.max_attempts.is_some_and(|n| attempts >= n) {
+            self.report(
+                &watchdog,
+                WatchdogAlert::TooManyAttempts,
+                name,
+                attempts,
+                &vars,
+            );
+        }
+        if watchdog
+            .max_duration
+            .is_some_and(|d| self.start.elapsed() > d)
+        {
+            self.report(&watchdog, WatchdogAlert::TooLong, name, attempts, &vars);
+        }
+    }
+
+    /// Remember the variables accessed by the current attempt, before its log is consumed to
+    /// wait on `retry`.
+    #[cfg(feature = "wait-on-retry")]
+    pub(crate) fn retried<'a, I>(&mut self, vars: I)
+    where
+        I: IntoIterator<Item = &'a VarRef>,
+    {
+        if self.watchdog.is_some() {
+            self.retried = Some(vars.into_iter().map(VarRef::id).collect());
+        }
+    }
+
+    /// Park the transaction `name` until one of `vars` changes, as notified through `ctrl`.
+    #[cfg(feature = "wait-on-retry")]
+    pub(crate) fn wait(
+        &mut self,
+        name: Option<&'static str>,
+        ctrl: &ControlBlock,
+        vars: &[VarRef],
+    ) {
+        let Some(watchdog) = self.watchdog.clone() else {
+            ctrl.wait();
+            return;
+        };
+        // The current attempt has not failed yet.
+        let attempts = self.n_failed + 1;
+        let ids = self
+            .retried
+            .clone()
+            .unwrap_or_else(|| vars.iter().map(VarRef::id).collect());
+        if vars.iter().all(VarRef::is_orphaned) {
+            self.report(&watchdog, WatchdogAlert::Orphaned, name, attempts, &ids);
+        }
+        if let Some(max) = watchdog.max_duration {
+            let remaining = max.saturating_sub(self.start.elapsed());
+            if ctrl.wait_timeout(remaining) {
+                return;
+            }
+            self.report(&watchdog, WatchdogAlert::TooLong, name, attempts, &ids);
+        }
+        ctrl.wait();
+    }
+
+    /// Call the callback of `watchdog`, unless `alert` was already reported.
+    fn report(
+        &mut self,
+        watchdog: &Watchdog,
+        alert: WatchdogAlert,
+        name: Option<&'static str>,
+        attempts: u64,
+        vars: &[VarId],
+    ) {
+        if self.reported.contains(&alert) {
+            return;
+        }
+        self.reported.push(alert);
+        (watchdog.callback)(&WatchdogReport {
+            alert,
+            name,
+            attempts,
+            elapsed: self.start.elapsed(),
+            vars: vars.to_vec(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[cfg(feature = "wait-on-retry")]
+    use crate::test;
+    use crate::{atomically, retry, StmError, TVar, Transaction};
+
+    /// The watchdog is global, so all scenarios run in a single test, and only reports of the
+    /// transactions of this test are kept.
+    #[test]
+    fn reports() {
+        static REPORTS: parking_lot::Mutex<Vec<WatchdogReport>> =
+            parking_lot::Mutex::new(Vec::new());
+
+        Watchdog::new(|report| {
+            if report
+                .name
+                .is_some_and(|name| name.starts_with("watchdog-"))
+            {
+                REPORTS.lock().push(report.clone());
+            }
+        })
+        .max_attempts(3)
+        .max_duration(Duration::from_millis(100))
+        .install();
+
+        let var = TVar::with_name(0, "counter");
+        let attempts = std::sync::atomic::AtomicU64::new(0);
+        Transaction::with_name("watchdog-attempts", |trans| {
+            var.read(trans)?;
+            if attempts.fetch_add(1, std::sync::atomic::Ordering::Relaxed) < 5 {
+                return Err(StmError::Failure);
+            }
+            Ok(())
+        });
+
+        // Each attempt that calls retry lets the writer change the flag once.
+        let flag = TVar::with_name(0, "flag");
+        let writer = flag.clone();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        std::thread::scope(|s| {
+            s.spawn(move || {
+                for i in 1..=3 {
+                    receiver.recv().unwrap();
+                    atomically(|trans| writer.write(trans, i));
+                }
+            });
+            Transaction::with_name("watchdog-retries", |trans| {
+                if flag.read(trans)? < 3 {
+                    // The writer stops listening once the flag is set.
+                    let _ = sender.send(());
+                    return retry();
+                }
+                Ok(())
+            });
+        });
+
+        #[cfg(feature = "wait-on-retry")]
+        let terminated = test::terminates(400, || {
+            Transaction::with_name("watchdog-orphaned", |trans| {
+                TVar::new(0).read(trans)?;
+                retry::<()>()
+            });
+        });
+
+        Watchdog::uninstall();
+        let reports = REPORTS.lock();
+        let find = |name, alert| {
+            reports
+                .iter()
+                .find(|report| report.name == Some(name) && report.alert == alert)
+                .unwrap_or_else(|| panic!("no {alert:?} report for {name}"))
+        };
+
+        let attempts = find("watchdog-attempts", WatchdogAlert::TooManyAttempts);
+        assert_eq!(attempts.attempts, 3);
+        assert_eq!(attempts.vars.len(), 1);
+        assert_eq!(attempts.vars[0].name(), Some("counter"));
+
+        // the read set of an attempt that called retry is reported too
+        let retries = find("watchdog-retries", WatchdogAlert::TooManyAttempts);
+        assert_eq!(retries.attempts, 3);
+        assert_eq!(retries.vars.len(), 1);
+        assert_eq!(retries.vars[0].name(), Some("flag"));
+
+        #[cfg(feature = "wait-on-retry")]
+        {
+            assert!(!terminated);
+            let alerts: Vec<_> = reports
+                .iter()
+                .filter(|report| report.name == Some("watchdog-orphaned"))
+                .map(|report| (report.name, report.alert))
+                .collect();
+            assert_eq!(
+                alerts,
+                [
+                    (Some("watchdog-orphaned"), WatchdogAlert::Orphaned),
+                    (Some("watchdog-orphaned"), WatchdogAlert::TooLong),
+                ]
+            );
+        }
+    }
+}
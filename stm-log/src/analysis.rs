@@ -0,0 +1,242 @@
+//! Reconstruction of the history of the variables from a log.
+
+use std::collections::BTreeMap;
+
+use crate::log::{Access, Attempt, Outcome, Var};
+
+/// Committed attempts, indexed by commit number.
+pub struct Commits<'a> {
+    by_seq: BTreeMap<u64, &'a Attempt>,
+}
+
+impl<'a> Commits<'a> {
+    pub fn new(log: &'a [Attempt]) -> Self {
+        let by_seq = log
+            .iter()
+            .filter_map(|attempt| attempt.seq.map(|seq| (seq, attempt)))
+            .collect();
+        Self { by_seq }
+    }
+
+    /// Iterate over the committed attempts, in serialization order.
+    pub fn iter(&self) -> impl Iterator<Item = (u64, &'a Attempt)> + '_ {
+        self.by_seq.iter().map(|(seq, attempt)| (*seq, *attempt))
+    }
+
+    /// Return the writes of `var` by the commits that happened while `attempt` ran.
+    pub fn writes_during(&self, attempt: &Attempt, var: &Var) -> Vec<CommittedWrite<'a>> {
+        self.by_seq
+            .range(attempt.from_seq..attempt.to_seq)
+            .filter_map(|(seq, commit)| {
+                commit
+                    .writes
+                    .iter()
+                    .find(|access| access.var == *var)
+                    .map(|access| (*seq, *commit, access))
+            })
+            .collect()
+    }
+}
+
+/// A committed write: the number of the commit, the committed attempt and the written value.
+pub type CommittedWrite<'a> = (u64, &'a Attempt, &'a Access);
+
+/// An attempt that failed because of a change of a variable, with the commits that may have
+/// made the change.
+pub struct Conflict<'a> {
+    pub attempt: &'a Attempt,
+    /// Variables that may have changed: the variable that failed validation, or the whole read
+    /// set for conflicts detected early.
+    pub vars: Vec<&'a Var>,
+    /// Commits that wrote one of the variables while the attempt ran.
+    pub causes: Vec<CommittedWrite<'a>>,
+}
+
+/// Find the conflicts of the log, in order of appearance.
+pub fn conflicts<'a>(log: &'a [Attempt], commits: &Commits<'a>) -> Vec<Conflict<'a>> {
+    log.iter()
+        .filter_map(|attempt| {
+            let vars: Vec<_> = match attempt.outcome {
+                Outcome::Conflict => attempt.var.iter().collect(),
+                Outcome::EarlyConflict => attempt.reads.iter().map(|access| &access.var).collect(),
+                _ => return None,
+            };
+            let mut causes: Vec<_> = vars
+                .iter()
+                .flat_map(|var| commits.writes_during(attempt, var))
+                .collect();
+            causes.sort_by_key(|(seq, _, _)| *seq);
+            Some(Conflict {
+                attempt,
+                vars,
+                causes,
+            })
+        })
+        .collect()
+}
+
+/// Return the values committed to the variables matching `pattern`, in serialization order.
+pub fn history<'a>(commits: &Commits<'a>, pattern: &str) -> Vec<CommittedWrite<'a>> {
+    commits
+        .iter()
+        .flat_map(|(seq, attempt)| {
+            attempt
+                .writes
+                .iter()
+                .filter(|access| access.var.matches(pattern))
+                .map(move |access| (seq, attempt, access))
+        })
+        .collect()
+}
+
+/// Count the outcomes of the attempts of each transaction name.
+pub fn summary(log: &[Attempt]) -> BTreeMap<Option<&str>, BTreeMap<Outcome, usize>> {
+    let mut summary: BTreeMap<_, BTreeMap<_, _>> = BTreeMap::new();
+    for attempt in log {
+        *summary
+            .entry(attempt.transaction.as_deref())
+            .or_default()
+            .entry(attempt.outcome)
+            .or_default() += 1;
+    }
+    summary
+}
+
+#[cfg(test)]
+mod test {
+    use std::io;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{mpsc, Arc, Mutex};
+    use std::thread;
+
+    use fast_stm::{atomically_named, EventLog, TVar};
+
+    use super::*;
+    use crate::log;
+
+    /// Two transactions increment `counter`, the second one conflicts with the first one, and
+    /// a third one reads an unnamed variable, which no transaction writes.
+    const LOG: &str = r#"
+{"thread":0,"transaction":"incr","attempt":1,"start_ns":0,"end_ns":10,"from_seq":0,"to_seq":0,"outcome":"commit","seq":0,"reads":[{"var":{"address":16,"name":"counter"},"value":"0"}],"writes":[{"var":{"address":16,"name":"counter"},"value":"1"}]}
+{"thread":1,"transaction":"incr","attempt":1,"start_ns":5,"end_ns":20,"from_seq":0,"to_seq":1,"outcome":"conflict","var":{"address":16,"name":"counter"},"reads":[{"var":{"address":16,"name":"counter"},"value":"0"}],"writes":[{"var":{"address":16,"name":"counter"},"value":"1"}]}
+{"thread":1,"transaction":"incr","attempt":2,"start_ns":20,"end_ns":30,"from_seq":1,"to_seq":1,"outcome":"commit","seq":1,"reads":[{"var":{"address":16,"name":"counter"},"value":"1"}],"writes":[{"var":{"address":16,"name":"counter"},"value":"2"}]}
+{"thread":2,"transaction":null,"attempt":1,"start_ns":0,"end_ns":40,"from_seq":0,"to_seq":2,"outcome":"early_conflict","reads":[{"var":{"address":32,"name":null}}],"writes":[]}
+"#;
+
+    #[test]
+    fn order() {
+        let log = log::read(LOG.as_bytes()).unwrap();
+        let commits = Commits::new(&log);
+        let order: Vec<_> = commits
+            .iter()
+            .map(|(seq, attempt)| (seq, attempt.thread))
+            .collect();
+        assert_eq!(order, [(0, 0), (1, 1)]);
+    }
+
+    #[test]
+    fn conflict_causes() {
+        let log = log::read(LOG.as_bytes()).unwrap();
+        let commits = Commits::new(&log);
+        let conflicts = conflicts(&log, &commits);
+
+        assert_eq!(conflicts.len(), 2);
+        assert_eq!(conflicts[0].attempt.thread, 1);
+        assert_eq!(conflicts[0].vars[0].name.as_deref(), Some("counter"));
+        assert_eq!(conflicts[0].causes.len(), 1);
+        assert_eq!(conflicts[0].causes[0].0, 0);
+        assert_eq!(conflicts[0].causes[0].2.value.as_deref(), Some("1"));
+        // nothing wrote the variable, it was changed outside of transactions
+        assert!(conflicts[1].causes.is_empty());
+    }
+
+    #[test]
+    fn counter_history() {
+        let log = log::read(LOG.as_bytes()).unwrap();
+        let commits = Commits::new(&log);
+        let values: Vec<_> = history(&commits, "counter")
+            .into_iter()
+            .map(|(seq, _, access)| (seq, access.value.as_deref()))
+            .collect();
+        assert_eq!(values, [(0, Some("1")), (1, Some("2"))]);
+        assert_eq!(history(&commits, "0x10").len(), 2);
+    }
+
+    #[test]
+    fn outcomes() {
+        let log = log::read(LOG.as_bytes()).unwrap();
+        let summary = summary(&log);
+        assert_eq!(summary[&Some("incr")][&Outcome::Commit], 2);
+        assert_eq!(summary[&Some("incr")][&Outcome::Conflict], 1);
+        assert_eq!(summary[&None][&Outcome::EarlyConflict], 1);
+    }
+
+    /// Output shared with the test.
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Record a conflict with `fast-stm`, and find its cause.
+    #[test]
+    fn recorded_conflict() {
+        let buffer = Buffer::default();
+        EventLog::render::<i32>();
+        EventLog::record(buffer.clone());
+
+        // "slow" reads the counter, then waits for "fast" to commit before writing it.
+        let var = TVar::with_name(0, "counter");
+        let varc = var.clone();
+        let (read_tx, read_rx) = mpsc::channel();
+        let (committed_tx, committed_rx) = mpsc::channel();
+        let slow = thread::spawn(move || {
+            let first = AtomicBool::new(true);
+            atomically_named("slow", |trans| {
+                let x = varc.read(trans)?;
+                if first.swap(false, Ordering::Relaxed) {
+                    read_tx.send(()).unwrap();
+                    committed_rx.recv().unwrap();
+                }
+                varc.write(trans, x + 10)
+            });
+        });
+        read_rx.recv().unwrap();
+        atomically_named("fast", |trans| var.write(trans, 32));
+        committed_tx.send(()).unwrap();
+        slow.join().unwrap();
+
+        EventLog::stop().unwrap();
+        let text = buffer.0.lock().unwrap().clone();
+        let log = log::read(text.as_slice()).unwrap();
+        let commits = Commits::new(&log);
+
+        let order: Vec<_> = commits
+            .iter()
+            .map(|(_, attempt)| attempt.transaction.as_deref())
+            .collect();
+        assert_eq!(order, [Some("fast"), Some("slow")]);
+
+        let conflicts = conflicts(&log, &commits);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].attempt.transaction.as_deref(), Some("slow"));
+        assert_eq!(conflicts[0].causes.len(), 1);
+        let (_, cause, access) = conflicts[0].causes[0];
+        assert_eq!(cause.transaction.as_deref(), Some("fast"));
+        assert_eq!(access.value.as_deref(), Some("32"));
+
+        let values: Vec<_> = history(&commits, "counter")
+            .into_iter()
+            .map(|(_, _, access)| access.value.as_deref())
+            .collect();
+        assert_eq!(values, [Some("32"), Some("42")]);
+    }
+}
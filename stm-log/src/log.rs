@@ -0,0 +1,109 @@
+//! Records of a log written by `fast_stm::EventLog`.
+
+use std::fmt;
+use std::io::{self, BufRead};
+
+use serde::Deserialize;
+
+/// Identifier of a variable.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize)]
+pub struct Var {
+    pub address: u64,
+    pub name: Option<String>,
+}
+
+impl Var {
+    /// Check if the variable is designated by `pattern`, either its name or its address.
+    pub fn matches(&self, pattern: &str) -> bool {
+        self.name.as_deref() == Some(pattern) || format!("{:#x}", self.address) == pattern
+    }
+}
+
+impl fmt::Display for Var {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "{name}@{:#x}", self.address),
+            None => write!(f, "{:#x}", self.address),
+        }
+    }
+}
+
+/// A variable read or written by an attempt, with its value if it was rendered.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Access {
+    pub var: Var,
+    pub value: Option<String>,
+}
+
+impl fmt::Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.value {
+            Some(value) => write!(f, "{} = {value}", self.var),
+            None => write!(f, "{}", self.var),
+        }
+    }
+}
+
+/// How an attempt ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Commit,
+    Conflict,
+    EarlyConflict,
+    Retry,
+    Failure,
+    Cancel,
+}
+
+/// An attempt of a transaction.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Attempt {
+    /// Number of the thread running the transaction.
+    pub thread: u64,
+    /// Name of the transaction, if any.
+    pub transaction: Option<String>,
+    /// Number of the attempt in the transaction, starting from 1.
+    pub attempt: u64,
+    pub start_ns: u64,
+    pub end_ns: u64,
+    /// Number of the first commit that may have happened during the attempt.
+    pub from_seq: u64,
+    /// Number of the first commit that happened after the attempt.
+    pub to_seq: u64,
+    pub outcome: Outcome,
+    /// Number of the commit, for committed attempts.
+    pub seq: Option<u64>,
+    /// Variable that failed validation, for conflicts.
+    pub var: Option<Var>,
+    pub reads: Vec<Access>,
+    pub writes: Vec<Access>,
+}
+
+impl fmt::Display for Attempt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "thread {} `{}` attempt {}",
+            self.thread,
+            self.transaction.as_deref().unwrap_or("<unnamed>"),
+            self.attempt
+        )
+    }
+}
+
+/// Read a log, one attempt per line.
+pub fn read<R: BufRead>(input: R) -> io::Result<Vec<Attempt>> {
+    let mut attempts = Vec::new();
+    for (i, line) in input.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let attempt = serde_json::from_str(&line).map_err(|err| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {err}", i + 1))
+        })?;
+        attempts.push(attempt);
+    }
+    Ok(attempts)
+}
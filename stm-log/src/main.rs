@@ -0,0 +1,131 @@
+//! Offline analysis of the transaction logs recorded by `fast_stm::EventLog`.
+//!
+//! ```text
+//! stm-log order <FILE>          list the commits in serialization order
+//! stm-log conflicts <FILE>      list the conflicts, with the commits that caused them
+//! stm-log history <VAR> <FILE>  list the values committed to a variable, by name or address
+//! stm-log summary <FILE>        count the outcomes of the attempts of each transaction
+//! ```
+
+mod analysis;
+mod log;
+
+use std::fs::File;
+use std::io::{self, BufReader, Write};
+use std::process::ExitCode;
+
+use analysis::Commits;
+use log::Attempt;
+
+const USAGE: &str = "\
+usage: stm-log <COMMAND> <FILE>
+
+commands:
+    order          list the commits in serialization order
+    conflicts      list the conflicts, with the commits that caused them
+    history <VAR>  list the values committed to a variable, by name or address
+    summary        count the outcomes of the attempts of each transaction";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (command, var, path) = match args.as_slice() {
+        [command, path] if command != "history" => (command.as_str(), None, path),
+        [command, var, path] if command == "history" => (command.as_str(), Some(var), path),
+        _ => {
+            eprintln!("{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+
+    let log = match File::open(path).and_then(|file| log::read(BufReader::new(file))) {
+        Ok(log) => log,
+        Err(err) => {
+            eprintln!("stm-log: cannot read {path}: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut out = io::stdout().lock();
+    let written = match (command, var) {
+        ("order", _) => order(&mut out, &log),
+        ("conflicts", _) => conflicts(&mut out, &log),
+        ("history", Some(var)) => history(&mut out, &log, var),
+        ("summary", _) => summary(&mut out, &log),
+        _ => {
+            eprintln!("{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+    match written {
+        Ok(()) => ExitCode::SUCCESS,
+        // the output was closed, e.g. by `head`
+        Err(err) if err.kind() == io::ErrorKind::BrokenPipe => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("stm-log: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn order(out: &mut impl Write, log: &[Attempt]) -> io::Result<()> {
+    for (seq, attempt) in Commits::new(log).iter() {
+        writeln!(
+            out,
+            "#{seq} {attempt} ({} ns)",
+            attempt.end_ns - attempt.start_ns
+        )?;
+        for access in &attempt.reads {
+            writeln!(out, "    read  {access}")?;
+        }
+        for access in &attempt.writes {
+            writeln!(out, "    wrote {access}")?;
+        }
+    }
+    Ok(())
+}
+
+fn conflicts(out: &mut impl Write, log: &[Attempt]) -> io::Result<()> {
+    let commits = Commits::new(log);
+    for conflict in analysis::conflicts(log, &commits) {
+        let attempt = conflict.attempt;
+        match &attempt.var {
+            Some(var) => writeln!(out, "{attempt}: failed validation of {var}")?,
+            None => writeln!(out, "{attempt}: read a variable that changed")?,
+        }
+        for (seq, commit, access) in &conflict.causes {
+            writeln!(out, "    #{seq} {commit} wrote {access}")?;
+        }
+        if conflict.causes.is_empty() {
+            let vars: Vec<_> = conflict.vars.iter().map(ToString::to_string).collect();
+            writeln!(
+                out,
+                "    no recorded commit wrote {} meanwhile, it was written outside of a transaction",
+                vars.join(", ")
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn history(out: &mut impl Write, log: &[Attempt], var: &str) -> io::Result<()> {
+    for (seq, attempt, access) in analysis::history(&Commits::new(log), var) {
+        writeln!(out, "#{seq} {attempt} wrote {access}")?;
+    }
+    Ok(())
+}
+
+fn summary(out: &mut impl Write, log: &[Attempt]) -> io::Result<()> {
+    for (name, outcomes) in analysis::summary(log) {
+        let outcomes: Vec<_> = outcomes
+            .iter()
+            .map(|(outcome, count)| format!("{outcome:?}: {count}"))
+            .collect();
+        writeln!(
+            out,
+            "`{}` {}",
+            name.unwrap_or("<unnamed>"),
+            outcomes.join(", ")
+        )?;
+    }
+    Ok(())
+}